@@ -0,0 +1,78 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use pnp::{Manifest, init_pnp_manifest};
+use std::hint::black_box;
+use std::path::Path;
+use std::time::Duration;
+
+const MANIFEST_STATE: &str = include_str!("../data/edge_case_manifest_state.json");
+
+fn bench_init_pnp_manifest(c: &mut Criterion) {
+    let manifest: Manifest = serde_json::from_str(MANIFEST_STATE).unwrap();
+
+    let manifest_path = Path::new("/path/to/project/.pnp.cjs");
+
+    // The manifest is cloned before each iteration so the trie doesn't accumulate entries
+    c.bench_function("init_pnp_manifest", |b| {
+        b.iter_batched(
+            || manifest.clone(),
+            |mut manifest| {
+                init_pnp_manifest(black_box(&mut manifest), black_box(manifest_path));
+                manifest
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+// Run with and without the `parallel` feature to compare both strategies
+fn bench_init_large_pnp_manifest(c: &mut Criterion) {
+    let packages: Vec<_> = (0..5000)
+        .map(|i| {
+            serde_json::json!([format!("pkg-{i}"), [["npm:1.0.0", {
+                "packageLocation": format!("./.yarn/cache/pkg-{i}-npm-1.0.0-abcdef.zip/node_modules/pkg-{i}/"),
+                "packageDependencies": [],
+            }]]])
+        })
+        .chain([serde_json::json!([null, [[null, {
+            "packageLocation": "./",
+            "packageDependencies": [],
+        }]]])])
+        .collect();
+
+    let manifest: Manifest = serde_json::from_value(serde_json::json!({
+        "enableTopLevelFallback": false,
+        "ignorePatternData": null,
+        "dependencyTreeRoots": [],
+        "fallbackPool": [],
+        "fallbackExclusionList": [],
+        "packageRegistryData": packages,
+    }))
+    .unwrap();
+
+    let manifest_path = Path::new("/path/to/project/.pnp.cjs");
+
+    let name = if cfg!(feature = "parallel") {
+        "init_pnp_manifest_5000_parallel"
+    } else {
+        "init_pnp_manifest_5000_sequential"
+    };
+
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            || manifest.clone(),
+            |mut manifest| {
+                init_pnp_manifest(black_box(&mut manifest), black_box(manifest_path));
+                manifest
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(1000).measurement_time(Duration::from_secs(10));
+    targets = bench_init_pnp_manifest, bench_init_large_pnp_manifest
+}
+
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use pnp::{Manifest, init_pnp_manifest, resolve_to_unqualified_via_manifest};
+use std::hint::black_box;
+use std::path::Path;
+use std::time::Duration;
+
+// Embedding the fixture ensures the benchmark fails to compile if it goes missing
+const TEST_EXPECTATIONS: &str = include_str!("../data/test-expectations.json");
+
+// The second test suite covers both regular dependencies and the fallback pool
+const FALLBACK_SUITE_INDEX: usize = 1;
+
+fn setup_manifest() -> Manifest {
+    let test_suites: Vec<serde_json::Value> = serde_json::from_str(TEST_EXPECTATIONS).unwrap();
+
+    let mut manifest: Manifest =
+        serde_json::from_value(test_suites[FALLBACK_SUITE_INDEX]["manifest"].clone()).unwrap();
+
+    init_pnp_manifest(&mut manifest, Path::new("/path/to/project/.pnp.cjs"));
+
+    manifest
+}
+
+fn bench_resolution(c: &mut Criterion) {
+    let manifest = setup_manifest();
+
+    let root_issuer = Path::new("/path/to/project/index.js");
+    let workspace_issuer = Path::new("/path/to/project/workspace-with-fallbacks/index.js");
+
+    c.bench_function("resolution_direct_dependency", |b| {
+        b.iter(|| {
+            let _ = resolve_to_unqualified_via_manifest(
+                black_box(&manifest),
+                black_box("test-1"),
+                black_box(root_issuer),
+            );
+        })
+    });
+
+    c.bench_function("resolution_fallback_pool", |b| {
+        b.iter(|| {
+            let _ = resolve_to_unqualified_via_manifest(
+                black_box(&manifest),
+                black_box("test-2"),
+                black_box(workspace_issuer),
+            );
+        })
+    });
+
+    c.bench_function("resolution_undeclared_dependency", |b| {
+        b.iter(|| {
+            let _ = resolve_to_unqualified_via_manifest(
+                black_box(&manifest),
+                black_box("test-3"),
+                black_box(workspace_issuer),
+            );
+        })
+    });
+
+    c.bench_function("resolution_deep_subpath", |b| {
+        b.iter(|| {
+            let _ = resolve_to_unqualified_via_manifest(
+                black_box(&manifest),
+                black_box("test-1/lib/very/deep/nested/path/index.js"),
+                black_box(root_issuer),
+            );
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(1000).measurement_time(Duration::from_secs(10));
+    targets = bench_resolution
+}
+
+criterion_main!(benches);
@@ -114,10 +114,35 @@ fn bench_vpath_mixed_workload(c: &mut Criterion) {
     });
 }
 
+fn bench_vpath_from_str(c: &mut Criterion) {
+    let paths = vec![
+        "/simple/native/path",
+        "/node_modules/.yarn/__virtual__/abc123/0/node_modules/package",
+        "/cache/package.zip/lib/index.js",
+        "/project/__virtual__/def456/2/node_modules/some-package/bundle.zip/dist/app.js",
+    ];
+
+    c.bench_function("vpath_from_path", |b| {
+        b.iter(|| {
+            for path in &paths {
+                let _ = VPath::from(black_box(Path::new(path)));
+            }
+        })
+    });
+
+    c.bench_function("vpath_from_str", |b| {
+        b.iter(|| {
+            for path in &paths {
+                let _ = VPath::from_str(black_box(path));
+            }
+        })
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(1000).measurement_time(Duration::from_secs(10));
-    targets = bench_vpath_native, bench_vpath_virtual, bench_vpath_zip, bench_vpath_virtual_zip, bench_vpath_edge_cases, bench_vpath_mixed_workload
+    targets = bench_vpath_native, bench_vpath_virtual, bench_vpath_zip, bench_vpath_virtual_zip, bench_vpath_edge_cases, bench_vpath_mixed_workload, bench_vpath_from_str
 }
 
 criterion_main!(benches);
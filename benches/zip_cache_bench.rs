@@ -0,0 +1,50 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use pnp::fs::{LruZipCache, ZipCache, open_zip_via_read_p};
+use std::hint::black_box;
+use std::time::Duration;
+
+const ZIP_PATHS: [&str; 3] = [
+    "data/@babel-plugin-syntax-dynamic-import-npm-7.8.3-fb9ff5634a-8.zip",
+    "fixtures/left-pad-1.zip",
+    "fixtures/left-pad-2.zip",
+];
+
+fn read_all(cache: &LruZipCache<Vec<u8>>) {
+    for zip_path in ZIP_PATHS {
+        black_box(cache.file_type(zip_path, "node_modules").unwrap());
+    }
+}
+
+fn bench_first_access(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zip_cache_first_access");
+
+    group.bench_function("cold", |b| {
+        b.iter_batched(
+            || LruZipCache::new(10, open_zip_via_read_p),
+            |cache| read_all(&cache),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("prefetched", |b| {
+        b.iter_batched(
+            || {
+                let cache = LruZipCache::new(10, open_zip_via_read_p);
+                assert!(cache.prefetch(ZIP_PATHS).is_empty());
+                cache
+            },
+            |cache| read_all(&cache),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(10));
+    targets = bench_first_access
+}
+
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+#!/usr/bin/env node
+/* eslint-disable */
+"use strict";
+
+const RAW_RUNTIME_STATE =
+'{\
+  "__metadata": {\
+    "version": 99,\
+    "hash": "0123456789abcdef"\
+  },\
+  "dependencyTreeRoots": [],\
+  "enableTopLevelFallback": false,\
+  "ignorePatternData": null,\
+  "fallbackExclusionList": [],\
+  "fallbackPool": [],\
+  "packageRegistryData": [\
+    [null, [\
+      [null, {\
+        "packageLocation": "./",\
+        "packageDependencies": []\
+      }]\
+    ]]\
+  ]\
+}';
@@ -0,0 +1,20 @@
+#!/usr/bin/env node
+/* eslint-disable */
+"use strict";
+
+const RAW_RUNTIME_STATE =
+'{\
+  "dependencyTreeRoots": [],\
+  "enableTopLevelFallback": false,\
+  "ignorePatternData": null,\
+  "fallbackExclusionList": [],\
+  "fallbackPool": [],\
+  "packageRegistryData": [\
+    [null, [\
+      [null, {\
+        "packageLocation": "./",\
+        "packageDependencies": []\
+      }]\
+    ]]\
+  ]\
+}';
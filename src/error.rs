@@ -1,4 +1,8 @@
-use std::path::PathBuf;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use thiserror::Error;
 
@@ -20,6 +24,61 @@ pub enum Error {
 
     #[error(transparent)]
     MissingDependency(Box<MissingDependency>),
+
+    #[error(transparent)]
+    Context(Box<ContextualError>),
+}
+
+impl Error {
+    /// Wraps the error into another one recording which resolution it
+    /// originated from.
+    pub fn with_context(self, specifier: &str, parent: &Path) -> Error {
+        Error::Context(Box::new(ContextualError {
+            specifier: specifier.to_string(),
+            parent: parent.to_path_buf(),
+            error: self,
+        }))
+    }
+
+    /// Returns the specifier that failed to resolve, if known.
+    pub fn specifier(&self) -> Option<&str> {
+        match self {
+            Error::BadSpecifier(_) | Error::FailedManifestHydration(_) => None,
+            Error::MissingPeerDependency(err) => Some(&err.request),
+            Error::UndeclaredDependency(err) => Some(&err.request),
+            Error::MissingDependency(err) => Some(&err.request),
+            Error::Context(context) => context.error.specifier().or(Some(&context.specifier)),
+        }
+    }
+
+    /// Returns the path of the file that issued the failing resolution, if
+    /// known.
+    pub fn issuer_path(&self) -> Option<&Path> {
+        match self {
+            Error::BadSpecifier(_) | Error::FailedManifestHydration(_) => None,
+            Error::MissingPeerDependency(err) => Some(&err.issuer_path),
+            Error::UndeclaredDependency(err) => Some(&err.issuer_path),
+            Error::MissingDependency(err) => Some(&err.issuer_path),
+            Error::Context(context) => context.error.issuer_path().or(Some(&context.parent)),
+        }
+    }
+
+    /// Returns the package that issued the failing resolution, if known.
+    pub fn issuer_locator(&self) -> Option<&PackageLocator> {
+        match self {
+            Error::BadSpecifier(_) | Error::FailedManifestHydration(_) => None,
+            Error::MissingPeerDependency(err) => Some(&err.issuer_locator),
+            Error::UndeclaredDependency(err) => Some(&err.issuer_locator),
+            Error::MissingDependency(err) => Some(&err.issuer_locator),
+            Error::Context(context) => context.error.issuer_locator(),
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> std::io::Error {
+        std::io::Error::other(err)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Error)]
@@ -29,11 +88,31 @@ pub struct BadSpecifier {
     pub specifier: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Error)]
-#[error("{message}")]
+#[derive(Debug, Clone)]
 pub struct FailedManifestHydration {
     pub message: String,
     pub manifest_path: PathBuf,
+
+    // Stored in an `Arc` rather than a `Box` so the error remains cloneable
+    pub source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl fmt::Display for FailedManifestHydration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for FailedManifestHydration {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl PartialEq for FailedManifestHydration {
+    fn eq(&self, other: &Self) -> bool {
+        self.message == other.message && self.manifest_path == other.manifest_path
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Error)]
@@ -74,3 +153,13 @@ pub struct MissingDependency {
     pub issuer_locator: PackageLocator,
     pub issuer_path: PathBuf,
 }
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("While resolving `{specifier}` from `{}`: {error}", parent.display())]
+pub struct ContextualError {
+    pub specifier: String,
+    pub parent: PathBuf,
+
+    #[source]
+    pub error: Error,
+}
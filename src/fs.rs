@@ -1,11 +1,18 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::Deserialize;
 use std::{
+    collections::VecDeque,
+    fmt,
     path::{Path, PathBuf},
     str::Utf8Error,
+    sync::{Arc, Mutex},
 };
 
 use crate::zip::Zip;
 
+pub use crate::zip::{Compression, IntegrityError, ZipBuilder, ZipError, ZipOpenOptions};
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum FileType {
     File,
@@ -27,6 +34,21 @@ pub struct VirtualInfo {
     pub virtual_segments: (String, String),
 }
 
+impl ZipInfo {
+    /// Returns the normalized path of the zip archive on disk, with the
+    /// virtual segments (if any) resolved.
+    pub fn physical_zip_path(&self) -> PathBuf {
+        PathBuf::from(crate::util::normalize_path(self.physical_base_path().to_string_lossy()))
+    }
+}
+
+impl VirtualInfo {
+    /// Returns the normalized path the virtual path points to on disk.
+    pub fn resolve_to_physical(&self) -> PathBuf {
+        PathBuf::from(crate::util::normalize_path(self.physical_base_path().to_string_lossy()))
+    }
+}
+
 pub trait VPathInfo {
     fn physical_base_path(&self) -> PathBuf;
 }
@@ -54,10 +76,68 @@ pub enum VPath {
     Native(PathBuf),
 }
 
+impl VPathInfo for VPath {
+    fn physical_base_path(&self) -> PathBuf {
+        match self {
+            VPath::Zip(info) => info.physical_base_path(),
+            VPath::Virtual(info) => info.physical_base_path(),
+            VPath::Native(path) => path.clone(),
+        }
+    }
+}
+
 impl VPath {
     pub fn from(p: &Path) -> std::io::Result<VPath> {
         vpath(p)
     }
+
+    /// Same as `VPath::from`, but skips the `Path` conversion for callers
+    /// that already have a string.
+    // Mirrors `VPath::from` rather than implementing `FromStr`, so that
+    // callers don't have to import the trait
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> std::io::Result<VPath> {
+        vpath_str(s)
+    }
+
+    pub fn into_zip_info(self) -> Option<ZipInfo> {
+        match self {
+            VPath::Zip(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn into_virtual_info(self) -> Option<VirtualInfo> {
+        match self {
+            VPath::Virtual(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn into_native(self) -> Option<PathBuf> {
+        match self {
+            VPath::Native(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl From<ZipInfo> for VPath {
+    fn from(info: ZipInfo) -> VPath {
+        VPath::Zip(info)
+    }
+}
+
+impl From<VirtualInfo> for VPath {
+    fn from(info: VirtualInfo) -> VPath {
+        VPath::Virtual(info)
+    }
+}
+
+impl From<PathBuf> for VPath {
+    fn from(path: PathBuf) -> VPath {
+        VPath::Native(path)
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -78,6 +158,15 @@ pub enum Error {
     IOError(#[from] std::io::Error),
 }
 
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> std::io::Error {
+        match err {
+            Error::IOError(err) => err,
+            err => std::io::Error::other(err),
+        }
+    }
+}
+
 #[cfg(feature = "mmap")]
 pub fn open_zip_via_mmap<P: AsRef<Path>>(p: P) -> Result<Zip<mmap_rs::Mmap>, std::io::Error> {
     let file = std::fs::File::open(p)?;
@@ -87,7 +176,7 @@ pub fn open_zip_via_mmap<P: AsRef<Path>>(p: P) -> Result<Zip<mmap_rs::Mmap>, std
 
     let mmap = unsafe { mmap_builder.with_file(&file, 0).map().unwrap() };
 
-    let zip = Zip::new(mmap).map_err(|_| std::io::Error::other("Failed to read the zip file"))?;
+    let zip = Zip::new(mmap)?;
 
     Ok(zip)
 }
@@ -100,7 +189,7 @@ pub fn open_zip_via_mmap_p(p: &Path) -> Result<Zip<mmap_rs::Mmap>, std::io::Erro
 pub fn open_zip_via_read<P: AsRef<Path>>(p: P) -> Result<Zip<Vec<u8>>, std::io::Error> {
     let data = std::fs::read(p)?;
 
-    let zip = Zip::new(data).map_err(|_| std::io::Error::other("Failed to read the zip file"))?;
+    let zip = Zip::new(data)?;
 
     Ok(zip)
 }
@@ -109,6 +198,42 @@ pub fn open_zip_via_read_p(p: &Path) -> Result<Zip<Vec<u8>>, std::io::Error> {
     open_zip_via_read(p)
 }
 
+/// Same as `open_zip_via_read`, but with the integrity of the archive being
+/// checked unless the options say otherwise.
+pub fn open_zip_via_read_with_options<P: AsRef<Path>>(
+    p: P,
+    options: &ZipOpenOptions,
+) -> Result<Zip<Vec<u8>>, std::io::Error> {
+    let data = std::fs::read(p)?;
+
+    let zip = Zip::new_with_options(data, options)?;
+
+    Ok(zip)
+}
+
+/// Opens the archive found while walking a directory. Entries that don't have
+/// a `.zip` extension (including compound ones such as `.yarn.zip`) are
+/// reported as not found.
+pub fn open_zip_via_from_dir_entry(
+    entry: std::fs::DirEntry,
+) -> Result<Zip<Vec<u8>>, std::io::Error> {
+    let file_name = entry.file_name();
+    let is_zip = file_name.to_str().is_some_and(|name| name.len() > 4 && name.ends_with(".zip"));
+
+    if !is_zip {
+        return Err(std::io::Error::from(std::io::ErrorKind::NotFound));
+    }
+
+    open_zip_via_read(entry.path())
+}
+
+/// Reads an archive that's already in memory, without copying it.
+pub fn open_zip_via_bytes(data: &[u8]) -> Result<Zip<&[u8]>, std::io::Error> {
+    let zip = Zip::new(data)?;
+
+    Ok(zip)
+}
+
 pub trait ZipCache<Storage>
 where
     Storage: AsRef<[u8]> + Send + Sync,
@@ -134,23 +259,156 @@ where
         zip_path: P,
         sub: S,
     ) -> Result<String, std::io::Error>;
+
+    /// Returns the number of archives currently kept open by the cache.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Opens the given archives (in parallel when the `parallel` feature is
+    /// enabled) so that the first accesses to their content don't have to.
+    /// Failing to open an archive isn't fatal; the errors are returned so
+    /// they can be reported.
+    fn prefetch<I, P>(&self, paths: I) -> Vec<std::io::Error>
+    where
+        Self: Sync,
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path> + Send,
+    {
+        #[cfg(feature = "parallel")]
+        let paths = paths.into_iter().collect::<Vec<P>>().into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let paths = paths.into_iter();
+
+        paths.filter_map(|p| self.act(p, |_| ()).err()).collect()
+    }
+}
+
+/// Decides which archive is closed when the cache is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evicts the least recently used archive.
+    #[default]
+    Lru,
+    /// Evicts the archive that was opened first, regardless of its accesses.
+    Fifo,
+    /// Evicts the first archive (in opening order) that wasn't accessed since
+    /// the last time the clock hand went over it.
+    Clock,
+}
+
+#[derive(Debug)]
+struct QueueEntry<Storage>
+where
+    Storage: AsRef<[u8]>,
+{
+    path: PathBuf,
+    zip: Arc<Zip<Storage>>,
+    referenced: bool,
+}
+
+// Backs both the FIFO and CLOCK policies; the queue is a circular buffer
+// starting at `hand`, and new entries are inserted right behind the hand so
+// that they are the last ones it reaches
+#[derive(Debug)]
+struct ZipQueue<Storage>
+where
+    Storage: AsRef<[u8]>,
+{
+    entries: VecDeque<QueueEntry<Storage>>,
+    hand: usize,
+}
+
+impl<Storage> ZipQueue<Storage>
+where
+    Storage: AsRef<[u8]>,
+{
+    fn get(&mut self, p: &Path, policy: EvictionPolicy) -> Option<Arc<Zip<Storage>>> {
+        let entry = self.entries.iter_mut().find(|entry| entry.path == p)?;
+
+        if policy == EvictionPolicy::Clock {
+            entry.referenced = true;
+        }
+
+        Some(entry.zip.clone())
+    }
+
+    fn insert(&mut self, path: PathBuf, zip: Arc<Zip<Storage>>, capacity: usize) {
+        while self.entries.len() >= capacity.max(1) {
+            self.hand %= self.entries.len();
+
+            let entry = &mut self.entries[self.hand];
+
+            if entry.referenced {
+                entry.referenced = false;
+                self.hand += 1;
+            } else {
+                self.entries.remove(self.hand);
+            }
+        }
+
+        let hand = self.hand.min(self.entries.len());
+
+        self.entries.insert(hand, QueueEntry { path, zip, referenced: false });
+        self.hand = hand + 1;
+    }
 }
 
 #[derive(Debug)]
+enum ZipCacheStore<Storage>
+where
+    Storage: AsRef<[u8]> + Send + Sync,
+{
+    Lru(concurrent_lru::sharded::LruCache<PathBuf, Zip<Storage>>),
+    Queue { policy: EvictionPolicy, queue: Mutex<ZipQueue<Storage>> },
+}
+
 pub struct LruZipCache<Storage>
 where
     Storage: AsRef<[u8]> + Send + Sync,
 {
-    lru: concurrent_lru::sharded::LruCache<PathBuf, Zip<Storage>>,
+    store: ZipCacheStore<Storage>,
+    capacity: u64,
     open: fn(&Path) -> std::io::Result<Zip<Storage>>,
 }
 
+// The cached archives are left out, as printing them would be very large
+impl<Storage> fmt::Debug for LruZipCache<Storage>
+where
+    Storage: AsRef<[u8]> + Send + Sync,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LruZipCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
 impl<Storage> LruZipCache<Storage>
 where
     Storage: AsRef<[u8]> + Send + Sync,
 {
     pub fn new(n: u64, open: fn(&Path) -> std::io::Result<Zip<Storage>>) -> LruZipCache<Storage> {
-        LruZipCache { lru: concurrent_lru::sharded::LruCache::new(n), open }
+        LruZipCache::with_policy(n, EvictionPolicy::Lru, open)
+    }
+
+    pub fn with_policy(
+        n: u64,
+        policy: EvictionPolicy,
+        open: fn(&Path) -> std::io::Result<Zip<Storage>>,
+    ) -> LruZipCache<Storage> {
+        let store = match policy {
+            EvictionPolicy::Lru => ZipCacheStore::Lru(concurrent_lru::sharded::LruCache::new(n)),
+            EvictionPolicy::Fifo | EvictionPolicy::Clock => ZipCacheStore::Queue {
+                policy,
+                queue: Mutex::new(ZipQueue { entries: VecDeque::new(), hand: 0 }),
+            },
+        };
+
+        LruZipCache { store, capacity: n, open }
     }
 }
 
@@ -163,9 +421,39 @@ where
         p: P,
         cb: F,
     ) -> Result<T, std::io::Error> {
-        let zip = self.lru.get_or_try_init(p.as_ref().to_path_buf(), 1, |p| (self.open)(p))?;
+        let (policy, capacity, queue) = match &self.store {
+            ZipCacheStore::Lru(lru) => {
+                let zip = lru.get_or_try_init(p.as_ref().to_path_buf(), 1, |p| (self.open)(p))?;
+
+                return Ok(cb(zip.value()));
+            }
+
+            ZipCacheStore::Queue { policy, queue } => {
+                (*policy, self.capacity.try_into().unwrap_or(usize::MAX), queue)
+            }
+        };
+
+        if let Some(zip) = queue.lock().unwrap().get(p.as_ref(), policy) {
+            return Ok(cb(&zip));
+        }
+
+        // The archive is opened without holding the lock; if another thread
+        // opened it in the meantime, its copy is the one that's kept
+        let zip = Arc::new((self.open)(p.as_ref())?);
+
+        let zip = {
+            let mut queue = queue.lock().unwrap();
+
+            match queue.get(p.as_ref(), policy) {
+                Some(zip) => zip,
+                None => {
+                    queue.insert(p.as_ref().to_path_buf(), zip.clone(), capacity);
+                    zip
+                }
+            }
+        };
 
-        Ok(cb(zip.value()))
+        Ok(cb(&zip))
     }
 
     fn file_type<P: AsRef<Path>, S: AsRef<str>>(
@@ -191,6 +479,75 @@ where
     ) -> Result<String, std::io::Error> {
         self.act(zip_path, |zip| zip.read_to_string(p.as_ref()))?
     }
+
+    fn len(&self) -> usize {
+        match &self.store {
+            ZipCacheStore::Lru(lru) => lru.total_charge().try_into().unwrap_or(usize::MAX),
+            ZipCacheStore::Queue { queue, .. } => queue.lock().unwrap().entries.len(),
+        }
+    }
+}
+
+/// A `ZipCache` that reads the content of its "archives" from regular
+/// directories: `root/foo.zip/bar/baz.txt` is read from the native file
+/// located at this exact same path. Mostly useful for tests, which can then
+/// work on unpacked fixtures rather than actual zip files.
+#[derive(Debug)]
+pub struct DirectoryZipCache {
+    root: PathBuf,
+}
+
+impl DirectoryZipCache {
+    pub fn new(root: PathBuf) -> DirectoryZipCache {
+        DirectoryZipCache { root }
+    }
+
+    fn entry_path<P: AsRef<Path>, S: AsRef<str>>(&self, zip_path: P, sub: S) -> PathBuf {
+        self.root.join(zip_path).join(sub.as_ref())
+    }
+}
+
+impl ZipCache<Vec<u8>> for DirectoryZipCache {
+    fn act<T, P: AsRef<Path>, F: FnOnce(&Zip<Vec<u8>>) -> T>(
+        &self,
+        p: P,
+        cb: F,
+    ) -> Result<T, std::io::Error> {
+        let zip = Zip::from_directory(&self.root.join(p))?;
+
+        Ok(cb(&zip))
+    }
+
+    fn file_type<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
+        zip_path: P,
+        p: S,
+    ) -> Result<FileType, std::io::Error> {
+        let metadata = std::fs::metadata(self.entry_path(zip_path, p))?;
+
+        if metadata.is_dir() { Ok(FileType::Directory) } else { Ok(FileType::File) }
+    }
+
+    fn read<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
+        zip_path: P,
+        p: S,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        std::fs::read(self.entry_path(zip_path, p))
+    }
+
+    fn read_to_string<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
+        zip_path: P,
+        p: S,
+    ) -> Result<String, std::io::Error> {
+        std::fs::read_to_string(self.entry_path(zip_path, p))
+    }
+
+    // Directories are read on every access; nothing is kept open
+    fn len(&self) -> usize {
+        0
+    }
 }
 
 fn vpath(p: &Path) -> std::io::Result<VPath> {
@@ -198,6 +555,10 @@ fn vpath(p: &Path) -> std::io::Result<VPath> {
         return Ok(VPath::Native(p.to_path_buf()));
     };
 
+    vpath_str(p_str)
+}
+
+fn vpath_str(p_str: &str) -> std::io::Result<VPath> {
     let normalized_path = crate::util::normalize_path(p_str);
 
     // We remove potential leading slashes to avoid __virtual__ accidentally removing them
@@ -222,6 +583,36 @@ fn vpath(p: &Path) -> std::io::Result<VPath> {
             continue;
         }
 
+        // A virtual package of a virtual package; the nested depth walks back
+        // through the internal segments first, then through the base ones
+        if let (true, Some(virtual_segments), Some(internal_segments)) =
+            (segment == "__virtual__", &mut virtual_items, &mut internal_items)
+        {
+            virtual_segments.push(segment);
+
+            if let Some(hash_segment) = segment_it.next() {
+                virtual_segments.push(hash_segment);
+            }
+
+            if let Some(depth_segment) = segment_it.next() {
+                let depth = depth_segment.parse::<usize>();
+
+                virtual_segments.push(depth_segment);
+
+                if let Ok(depth) = depth {
+                    let internal_depth = depth.min(internal_segments.len());
+                    internal_segments.truncate(internal_segments.len() - internal_depth);
+
+                    let parent_segments = base_items
+                        .split_off(base_items.len().saturating_sub(depth - internal_depth));
+
+                    virtual_segments.splice(0..0, parent_segments);
+                }
+            }
+
+            continue;
+        }
+
         if segment == "__virtual__" && virtual_items.is_none() {
             let mut acc_segments = Vec::with_capacity(3);
 
@@ -253,6 +644,8 @@ fn vpath(p: &Path) -> std::io::Result<VPath> {
             continue;
         }
 
+        // Any segment with a `.zip` extension is an archive, including the
+        // compound ones such as `.yarn.zip`, but a bare `.zip` isn't
         if segment.len() > 4 && segment.ends_with(".zip") {
             zip_items = Some(Vec::with_capacity(10));
         }
@@ -349,6 +742,28 @@ mod tests {
         zip.file_type("not_exists").unwrap();
     }
 
+    #[test]
+    fn test_zip_via_bytes() {
+        let zip = open_zip_via_bytes(include_bytes!(
+            "../data/@babel-plugin-syntax-dynamic-import-npm-7.8.3-fb9ff5634a-8.zip"
+        ))
+        .unwrap();
+
+        assert_eq!(zip.file_type("node_modules").unwrap(), FileType::Directory);
+        assert!(
+            zip.read_to_string("node_modules/@babel/plugin-syntax-dynamic-import/package.json")
+                .unwrap()
+                .contains("\"name\": \"@babel/plugin-syntax-dynamic-import\"")
+        );
+
+        let err = open_zip_via_bytes(b"not a zip file, but long enough for a record").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<ZipError>(),
+            Some(ZipError::EndOfCentralDirectoryNotFound)
+        ));
+    }
+
     #[test]
     fn test_zip_list() {
         let zip = open_zip_via_read(PathBuf::from(
@@ -400,6 +815,235 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_vpath_conversions() {
+        let zip_info = ZipInfo {
+            base_path: "/path/to/foo.zip".to_string(),
+            virtual_segments: None,
+            zip_path: "node_modules/foo/index.js".to_string(),
+        };
+
+        let vpath: VPath = zip_info.clone().into();
+        assert_eq!(vpath.clone().into_zip_info(), Some(zip_info));
+        assert_eq!(vpath.clone().into_virtual_info(), None);
+        assert_eq!(vpath.into_native(), None);
+
+        let virtual_info = VirtualInfo {
+            base_path: "/path/to/__virtual__".to_string(),
+            virtual_segments: ("a/0".to_string(), "foo".to_string()),
+        };
+
+        let vpath: VPath = virtual_info.clone().into();
+        assert_eq!(vpath.clone().into_virtual_info(), Some(virtual_info));
+        assert_eq!(vpath.into_zip_info(), None);
+
+        let vpath: VPath = PathBuf::from("/path/to/foo").into();
+        assert_eq!(vpath.into_native(), Some(PathBuf::from("/path/to/foo")));
+    }
+
+    #[test]
+    fn test_open_zip_via_from_dir_entry() {
+        let mut zip_count = 0;
+
+        for entry in std::fs::read_dir("fixtures").unwrap() {
+            let entry = entry.unwrap();
+            let is_zip = entry.path().extension().is_some_and(|ext| ext == "zip");
+
+            match open_zip_via_from_dir_entry(entry) {
+                Ok(zip) => {
+                    assert!(is_zip);
+                    assert!(!zip.is_empty());
+                    zip_count += 1;
+                }
+                Err(err) => {
+                    assert!(!is_zip);
+                    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+                }
+            }
+        }
+
+        assert!(zip_count > 0);
+    }
+
+    #[test]
+    fn test_vpath_physical_base_path() {
+        let vpath = VPath::from(Path::new("/a/b/c.zip/d/e")).unwrap();
+        assert_eq!(vpath.physical_base_path(), PathBuf::from("/a/b/c.zip"));
+
+        let vpath = VPath::from(Path::new("/a/__virtual__/abc/0/b/c")).unwrap();
+        assert_eq!(vpath.physical_base_path(), PathBuf::from("/a/b/c"));
+
+        let vpath = VPath::from(Path::new("/a/b/c")).unwrap();
+        assert_eq!(vpath.physical_base_path(), PathBuf::from("/a/b/c"));
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let cache = LruZipCache::new(10, open_zip_via_read_p);
+
+        let errors = cache.prefetch([
+            "fixtures/left-pad-1.zip",
+            "fixtures/left-pad-2.zip",
+            "fixtures/does-not-exist.zip",
+        ]);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), std::io::ErrorKind::NotFound);
+
+        assert_eq!(
+            cache.file_type("fixtures/left-pad-1.zip", "node_modules/left-pad").unwrap(),
+            FileType::Directory
+        );
+    }
+
+    // The LRU store is sharded using a randomly seeded hasher, so which
+    // archives compete for the same slot varies between runs and its eviction
+    // order can't be checked here.
+    //
+    // In the first sequence, the first archive is the oldest one, but also the
+    // most recently used one when the third archive gets opened. In the second
+    // one, the third archive must survive the opening of the fourth, as CLOCK
+    // inserts new entries behind its hand.
+    #[rstest]
+    #[case(EvictionPolicy::Fifo, &[0, 1, 0, 2, 0], 4)]
+    #[case(EvictionPolicy::Clock, &[0, 1, 0, 2, 0], 3)]
+    #[case(EvictionPolicy::Clock, &[0, 1, 0, 2, 3, 2], 4)]
+    fn test_eviction_policy(
+        #[case] policy: EvictionPolicy,
+        #[case] accesses: &[usize],
+        #[case] expected_opens: usize,
+    ) {
+        thread_local! {
+            static OPENS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+
+        fn open(p: &Path) -> std::io::Result<Zip<Vec<u8>>> {
+            OPENS.set(OPENS.get() + 1);
+            open_zip_via_read_p(p)
+        }
+
+        OPENS.set(0);
+
+        let cache = LruZipCache::with_policy(2, policy, open);
+
+        let zip_paths = [
+            "fixtures/left-pad-1.zip",
+            "fixtures/left-pad-2.zip",
+            "fixtures/left-pad-diff-old.zip",
+            "fixtures/left-pad-comment.zip",
+        ];
+
+        for &index in accesses {
+            cache.file_type(zip_paths[index], "node_modules").unwrap();
+        }
+
+        assert_eq!(OPENS.get(), expected_opens);
+    }
+
+    #[rstest]
+    #[case(EvictionPolicy::Lru)]
+    #[case(EvictionPolicy::Fifo)]
+    fn test_lru_zip_cache_debug(#[case] policy: EvictionPolicy) {
+        let cache = LruZipCache::with_policy(4, policy, open_zip_via_read_p);
+        assert_eq!(format!("{cache:?}"), "LruZipCache { capacity: 4, len: 0 }");
+
+        cache.file_type("fixtures/left-pad-1.zip", "node_modules").unwrap();
+        assert_eq!(format!("{cache:?}"), "LruZipCache { capacity: 4, len: 1 }");
+    }
+
+    #[rstest]
+    #[case(EvictionPolicy::Lru)]
+    #[case(EvictionPolicy::Fifo)]
+    #[case(EvictionPolicy::Clock)]
+    fn test_zip_cache_len(#[case] policy: EvictionPolicy) {
+        // The LRU store splits its capacity between 16 shards; this leaves
+        // room for two archives even if they end up in the same one
+        let cache = LruZipCache::with_policy(32, policy, open_zip_via_read_p);
+        assert_eq!(cache.len(), 0);
+        assert!(cache.is_empty());
+
+        for zip_path in
+            ["fixtures/left-pad-1.zip", "fixtures/left-pad-2.zip", "fixtures/left-pad-1.zip"]
+        {
+            cache.file_type(zip_path, "node_modules").unwrap();
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.is_empty());
+    }
+
+    #[test]
+    fn test_open_zip_via_read_with_options() {
+        let options = ZipOpenOptions::default();
+        assert!(open_zip_via_read_with_options("fixtures/left-pad-1.zip", &options).is_ok());
+
+        let mut builder = ZipBuilder::new();
+        builder.add_file("a.txt", b"hello world", Compression::Uncompressed);
+        let mut data = builder.finish();
+
+        let offset = data.windows(11).position(|w| w == b"hello world").unwrap();
+        data[offset] = b'j';
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let corrupted_path = temp_dir.path().join("corrupted.zip");
+        std::fs::write(&corrupted_path, data).unwrap();
+
+        let err = open_zip_via_read_with_options(&corrupted_path, &options).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<ZipError>(),
+            Some(ZipError::IntegrityCheckFailed(_))
+        ));
+
+        let options = ZipOpenOptions { skip_integrity_check: true };
+        assert!(open_zip_via_read_with_options(&corrupted_path, &options).is_ok());
+    }
+
+    #[test]
+    fn test_error_into_io_error() {
+        let err: std::io::Error = Error::EntryNotFound.into();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<Error>(),
+            Some(Error::EntryNotFound)
+        ));
+
+        let err: std::io::Error =
+            Error::IOError(std::io::Error::from(std::io::ErrorKind::NotFound)).into();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_directory_zip_cache() {
+        let cache = DirectoryZipCache::new(PathBuf::from("fixtures/unpacked"));
+
+        assert_eq!(
+            cache.file_type("left-pad.zip", "node_modules/left-pad").unwrap(),
+            FileType::Directory
+        );
+        assert_eq!(
+            cache.file_type("left-pad.zip", "node_modules/left-pad/index.js").unwrap(),
+            FileType::File
+        );
+
+        let res =
+            cache.read_to_string("left-pad.zip", "node_modules/left-pad/package.json").unwrap();
+        assert!(res.contains("\"name\": \"left-pad\""));
+
+        let err = cache.read("left-pad.zip", "node_modules/left-pad/not_exists").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        let via_zip = cache
+            .act("left-pad.zip", |zip| {
+                assert_eq!(zip.file_type("node_modules/left-pad").unwrap(), FileType::Directory);
+                zip.read_to_string("node_modules/left-pad/package.json").unwrap()
+            })
+            .unwrap();
+        assert_eq!(via_zip, res);
+
+        assert!(cache.is_empty());
+    }
+
     #[rstest]
     #[case(".zip", None)]
     #[case("foo", None)]
@@ -467,6 +1111,34 @@ mod tests {
         virtual_segments: None,
         zip_path: "d".into(),
     })))]
+    #[case("./a/b/c/foo.yarn.zip", None)]
+    #[case("./a/b/c/foo.yarn.zip/bar", Some(VPath::Zip(ZipInfo {
+        base_path: "a/b/c/foo.yarn.zip".into(),
+        virtual_segments: None,
+        zip_path: "bar".into(),
+    })))]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/foo.yarn.zip/bar", Some(VPath::Zip(ZipInfo {
+        base_path: "a/b".into(),
+        virtual_segments: Some(("__virtual__/foo-abcdef/0/c/foo.yarn.zip".into(), "c/foo.yarn.zip".into())),
+        zip_path: "bar".into(),
+    })))]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/0/d", Some(VPath::Virtual(VirtualInfo {
+        base_path: "a/b".into(),
+        virtual_segments: ("__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/0/d".into(), "c/d".into()),
+    })))]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/1/d", Some(VPath::Virtual(VirtualInfo {
+        base_path: "a/b".into(),
+        virtual_segments: ("__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/1/d".into(), "d".into()),
+    })))]
+    #[case("./a/b/__virtual__/foo-abcdef/1/c/__virtual__/bar-abcdef/2/d", Some(VPath::Virtual(VirtualInfo {
+        base_path: "".into(),
+        virtual_segments: ("a/b/__virtual__/foo-abcdef/1/c/__virtual__/bar-abcdef/2/d".into(), "d".into()),
+    })))]
+    #[case("/a/b/__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/1/d/foo.zip/bar", Some(VPath::Zip(ZipInfo {
+        base_path: "/a/b".into(),
+        virtual_segments: Some(("__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/1/d/foo.zip".into(), "d/foo.zip".into())),
+        zip_path: "bar".into(),
+    })))]
     fn test_path_to_pnp(#[case] input: &str, #[case] expected: Option<VPath>) {
         let expectation: VPath = match &expected {
             Some(p) => p.clone(),
@@ -481,5 +1153,35 @@ mod tests {
                 panic!("{input:?}: {err}");
             }
         }
+
+        assert_eq!(VPath::from_str(input).unwrap(), expectation, "input='{input:?}'");
+    }
+
+    #[rstest]
+    #[case("foo.zip/bar", "foo.zip")]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/d", "a/b/c/d")]
+    #[case("./a/b/__virtual__/foo-abcdef/1/c/d", "a/c/d")]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/foo.zip/bar", "a/b/c/foo.zip")]
+    #[case("./a/b/__virtual__/foo-abcdef/1/c/foo.zip/bar", "a/c/foo.zip")]
+    #[case("/a/b/__virtual__/foo-abcdef/1/c/foo.zip/bar", "/a/c/foo.zip")]
+    #[case("/a/b/__virtual__/foo-abcdef/2/c/foo.zip/bar", "/c/foo.zip")]
+    #[case("/__virtual__/foo-abcdef/2/c/foo.zip/bar", "/c/foo.zip")]
+    #[case("./a/b/c/foo.zip/bar/baz/qux.zip", "a/b/c/foo.zip")]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/0/d", "a/b/c/d")]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/1/d", "a/b/d")]
+    #[case("./a/b/__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/2/d", "a/d")]
+    #[case("./a/b/__virtual__/foo-abcdef/1/c/__virtual__/bar-abcdef/2/d", "d")]
+    #[case(
+        "/a/b/__virtual__/foo-abcdef/0/c/__virtual__/bar-abcdef/1/d/foo.zip/bar",
+        "/a/b/d/foo.zip"
+    )]
+    fn test_physical_path(#[case] input: &str, #[case] expected: &str) {
+        let physical_path = match vpath(&PathBuf::from(input)).unwrap() {
+            VPath::Zip(info) => info.physical_zip_path(),
+            VPath::Virtual(info) => info.resolve_to_physical(),
+            VPath::Native(_) => panic!("{input:?} shouldn't be a native path"),
+        };
+
+        assert_eq!(physical_path, PathBuf::from(expected), "input='{input:?}'");
     }
 }
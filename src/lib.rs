@@ -3,73 +3,200 @@ pub mod fs;
 mod error;
 mod manifest;
 mod util;
+#[cfg(feature = "watch")]
+mod watch;
 mod zip;
 
 use std::{
-    collections::hash_map::Entry,
+    collections::VecDeque,
+    fmt,
     path::{Path, PathBuf},
-    sync::OnceLock,
+    sync::{Arc, LazyLock, OnceLock, RwLock},
+    time::SystemTime,
 };
 
 use fancy_regex::Regex;
+use indexmap::map::Entry;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize, Serializer};
 
 pub use crate::{
     error::{
-        BadSpecifier, Error, FailedManifestHydration, MissingDependency, MissingPeerDependency,
-        UndeclaredDependency,
+        BadSpecifier, ContextualError, Error, FailedManifestHydration, MissingDependency,
+        MissingPeerDependency, UndeclaredDependency,
     },
-    manifest::{Manifest, PackageDependency, PackageInformation, PackageLocator},
+    manifest::{
+        CURRENT_SUPPORTED_MANIFEST_VERSION, Manifest, ManifestDiff, ManifestMetadata,
+        ManifestWarning, PackageDependency, PackageInformation, PackageInformationBuilder,
+        PackageIter, PackageLocator,
+    },
+    util::{from_portable_path, percent_decode_path, percent_encode_path, to_portable_path},
 };
 
-#[derive(Debug)]
+#[cfg(feature = "watch")]
+pub use crate::watch::PnpManifestWatcher;
+
+// Resolution events are only reported when the `tracing` feature is enabled;
+// otherwise the macro expands to nothing, arguments included
+#[cfg(feature = "tracing")]
+macro_rules! debug_event {
+    ($($arg:tt)*) => { tracing::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug_event {
+    ($($arg:tt)*) => {};
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(from = "SerializedResolution<PathBuf, String>")]
 pub enum Resolution {
     Resolved(PathBuf, Option<String>),
     Skipped,
 }
 
+// Resolutions are serialized as `{"type": "resolved", "path": ..., "subpath": ...}`
+// or `{"type": "skipped"}`, which tuple variants can't be tagged as
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum SerializedResolution<P, S> {
+    Resolved { path: P, subpath: Option<S> },
+    Skipped,
+}
+
+impl From<SerializedResolution<PathBuf, String>> for Resolution {
+    fn from(resolution: SerializedResolution<PathBuf, String>) -> Resolution {
+        match resolution {
+            SerializedResolution::Resolved { path, subpath } => Resolution::Resolved(path, subpath),
+            SerializedResolution::Skipped => Resolution::Skipped,
+        }
+    }
+}
+
+impl Serialize for Resolution {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Resolution::Resolved(path, subpath) => {
+                SerializedResolution::Resolved { path, subpath: subpath.as_ref() }
+            }
+            Resolution::Skipped => SerializedResolution::Skipped,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The filenames looked up (in order) when searching for a PnP manifest.
+pub const DEFAULT_MANIFEST_FILENAMES: &[&str] = &[".pnp.cjs", ".pnp.mjs", ".pnp.js"];
+
+#[derive(Clone)]
 pub struct ResolutionHost {
     #[allow(clippy::type_complexity)]
-    pub find_pnp_manifest: Box<dyn Fn(&Path) -> Result<Option<Manifest>, Error>>,
+    pub find_pnp_manifest: Arc<dyn Fn(&Path) -> Result<Option<Manifest>, Error> + Send + Sync>,
+}
+
+impl ResolutionHost {
+    /// Creates a host whose `find_pnp_manifest` looks for the given manifest
+    /// filenames rather than the default ones.
+    pub fn with_manifest_filenames(manifest_filenames: Vec<String>) -> ResolutionHost {
+        ResolutionHost {
+            find_pnp_manifest: Arc::new(move |parent| {
+                let filenames: Vec<&str> = manifest_filenames.iter().map(String::as_str).collect();
+                find_pnp_manifest_named(parent, &filenames)
+            }),
+        }
+    }
 }
 
 impl Default for ResolutionHost {
     fn default() -> ResolutionHost {
-        ResolutionHost { find_pnp_manifest: Box::new(find_pnp_manifest) }
+        ResolutionHost { find_pnp_manifest: Arc::new(find_pnp_manifest) }
     }
 }
 
-#[derive(Default)]
+/// Chains multiple hosts together. The hosts are queried in the order they
+/// were provided, and the first one to find a manifest wins; errors are
+/// returned immediately without querying the following hosts.
+pub struct FallbackResolutionHost {
+    hosts: Vec<ResolutionHost>,
+}
+
+impl FallbackResolutionHost {
+    pub fn new(hosts: Vec<ResolutionHost>) -> FallbackResolutionHost {
+        FallbackResolutionHost { hosts }
+    }
+
+    pub fn find_pnp_manifest(&self, parent: &Path) -> Result<Option<Manifest>, Error> {
+        for host in &self.hosts {
+            if let Some(manifest) = (host.find_pnp_manifest)(parent)? {
+                return Ok(Some(manifest));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl From<FallbackResolutionHost> for ResolutionHost {
+    fn from(host: FallbackResolutionHost) -> ResolutionHost {
+        ResolutionHost { find_pnp_manifest: Arc::new(move |parent| host.find_pnp_manifest(parent)) }
+    }
+}
+
+/// Callback receiving the requested package name and the issuer locator of a
+/// dependency resolved through the fallback pool.
+pub type FallbackCallback = dyn Fn(&str, &PackageLocator) + Send + Sync;
+
+#[derive(Clone, Default)]
 pub struct ResolutionConfig {
     pub host: ResolutionHost,
+
+    /// If set, resolutions that wouldn't be handled by PnP (for example
+    /// because no manifest could be found) are reported as errors rather than
+    /// returning `Resolution::Skipped`.
+    pub strict: bool,
+
+    /// Called with the requested package name and the issuer locator each
+    /// time a dependency gets resolved through the fallback pool, which
+    /// usually means that the issuer forgot to declare it.
+    pub on_fallback: Option<Arc<FallbackCallback>>,
+
+    /// If set, the manifest is first looked up in this environment variable
+    /// (see `load_pnp_manifest_from_env_var`), and the host is only queried
+    /// when the variable isn't set. The decoded manifest is reused until the
+    /// value of the variable changes.
+    pub env_manifest_var: Option<String>,
 }
 
-fn parse_scoped_package_name(specifier: &str) -> Option<(String, Option<String>)> {
+fn parse_scoped_package_name(specifier: &str) -> Option<(&str, Option<&str>)> {
     let mut segments = specifier.splitn(3, '/');
 
     let scope = segments.next()?;
 
     let name = segments.next()?;
 
-    let package_name = specifier[..scope.len() + name.len() + 1].to_string();
+    let package_name = &specifier[..scope.len() + name.len() + 1];
 
-    let subpath = segments.next().map(|v| v.to_string());
+    let subpath = segments.next();
 
     Some((package_name, subpath))
 }
 
-fn parse_global_package_name(specifier: &str) -> Option<(String, Option<String>)> {
+fn parse_global_package_name(specifier: &str) -> Option<(&str, Option<&str>)> {
     let mut segments = specifier.splitn(2, '/');
 
     let name = segments.next()?;
 
-    let package_name = name.to_string();
+    let subpath = segments.next();
 
-    let subpath = segments.next().map(|v| v.to_string());
-
-    Some((package_name, subpath))
+    Some((name, subpath))
 }
 
-pub fn parse_bare_identifier(specifier: &str) -> Result<(String, Option<String>), Error> {
+fn parse_bare_identifier_ref(specifier: &str) -> Result<(&str, Option<&str>), Error> {
     let name = if specifier.starts_with('@') {
         parse_scoped_package_name(specifier)
     } else {
@@ -84,11 +211,70 @@ pub fn parse_bare_identifier(specifier: &str) -> Result<(String, Option<String>)
     })
 }
 
+/// A bare specifier split into the name of the package it refers to and the
+/// path requested within it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BareIdentifier {
+    pub name: String,
+    pub subpath: Option<String>,
+}
+
+impl fmt::Display for BareIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.subpath {
+            Some(subpath) => write!(f, "{}/{subpath}", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+pub fn parse_bare_identifier(specifier: &str) -> Result<BareIdentifier, Error> {
+    let (name, subpath) = parse_bare_identifier_ref(specifier)?;
+
+    Ok(BareIdentifier { name: name.to_string(), subpath: subpath.map(|v| v.to_string()) })
+}
+
+#[deprecated(note = "Use `parse_bare_identifier` instead")]
+pub fn parse_bare_identifier_tuple(specifier: &str) -> Result<(String, Option<String>), Error> {
+    parse_bare_identifier(specifier).map(|identifier| (identifier.name, identifier.subpath))
+}
+
 pub fn find_closest_pnp_manifest_path(path: &Path) -> Option<PathBuf> {
+    find_closest_pnp_manifest_path_named(path, DEFAULT_MANIFEST_FILENAMES)
+}
+
+pub fn find_closest_pnp_manifest_path_named(path: &Path, filenames: &[&str]) -> Option<PathBuf> {
     for p in path.ancestors() {
-        let pnp_path = p.join(".pnp.cjs");
-        if pnp_path.exists() {
-            return Some(pnp_path);
+        for filename in filenames {
+            let pnp_path = p.join(filename);
+            if pnp_path.exists() {
+                return Some(pnp_path);
+            }
+        }
+    }
+    None
+}
+
+/// Same as `find_closest_pnp_manifest_path`, except that the `pnpDataPath`
+/// setting from the `.yarnrc.yml` files found along the way takes precedence
+/// over the default manifest filenames.
+#[cfg(feature = "yarnrc")]
+pub fn find_closest_pnp_manifest_path_respecting_yarnrc(path: &Path) -> Option<PathBuf> {
+    for p in path.ancestors() {
+        if let Ok(yarnrc) = std::fs::read_to_string(p.join(".yarnrc.yml")) {
+            if let Some(pnp_data_path) = util::read_yarnrc_setting(&yarnrc, "pnpDataPath") {
+                let pnp_path = p.join(pnp_data_path);
+                if pnp_path.exists() {
+                    return Some(pnp_path);
+                }
+            }
+        }
+
+        for filename in DEFAULT_MANIFEST_FILENAMES {
+            let pnp_path = p.join(filename);
+            if pnp_path.exists() {
+                return Some(pnp_path);
+            }
         }
     }
     None
@@ -97,10 +283,9 @@ pub fn find_closest_pnp_manifest_path(path: &Path) -> Option<PathBuf> {
 pub fn load_pnp_manifest(p: &Path) -> Result<Manifest, Error> {
     let manifest_content = std::fs::read_to_string(p).map_err(|err| {
         Error::FailedManifestHydration(Box::new(FailedManifestHydration {
-            message: format!(
-                "We failed to read the content of the manifest.\n\nOriginal error: {err}"
-            ),
+            message: String::from("We failed to read the content of the manifest."),
             manifest_path: p.to_path_buf(),
+            source: Some(Arc::new(err)),
         }))
     })?;
 
@@ -118,6 +303,7 @@ pub fn load_pnp_manifest(p: &Path) -> Result<Manifest, Error> {
         .ok_or_else(|| Error::FailedManifestHydration(Box::new(FailedManifestHydration {
             message: String::from("We failed to locate the PnP data payload inside its manifest file. Did you manually edit the file?"),
             manifest_path: p.to_path_buf(),
+            source: None,
         })))?;
 
     let iter = manifest_content.chars().skip(manifest_match.end());
@@ -139,18 +325,236 @@ pub fn load_pnp_manifest(p: &Path) -> Result<Manifest, Error> {
         }
     }
 
-    let mut manifest: Manifest = serde_json::from_str(&json_string)
+    let mut manifest = parse_pnp_manifest_json(&json_string, p)?;
+
+    init_pnp_manifest(&mut manifest, p);
+
+    Ok(manifest)
+}
+
+fn parse_pnp_manifest_json(json_string: &str, p: &Path) -> Result<Manifest, Error> {
+    let manifest: Manifest = serde_json::from_str(json_string)
         .map_err(|err| Error::FailedManifestHydration(Box::new(FailedManifestHydration {
-            message: format!("We failed to parse the PnP data payload as proper JSON; Did you manually edit the file?\n\nOriginal error: {err}"),
+            message: String::from("We failed to parse the PnP data payload as proper JSON; Did you manually edit the file?"),
             manifest_path: p.to_path_buf(),
+            source: Some(Arc::new(err)),
         })))?;
 
-    init_pnp_manifest(&mut manifest, p);
+    if let Some(metadata) = &manifest.metadata {
+        if metadata.version > CURRENT_SUPPORTED_MANIFEST_VERSION {
+            return Err(Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+                message: format!(
+                    "The PnP data payload uses an unsupported manifest version ({}); the latest version supported is {CURRENT_SUPPORTED_MANIFEST_VERSION}. Try upgrading the pnp crate.",
+                    metadata.version
+                ),
+                manifest_path: p.to_path_buf(),
+                source: None,
+            })));
+        }
+    }
 
     Ok(manifest)
 }
 
+/// The environment variable checked by `load_pnp_manifest_from_env`.
+pub const DEFAULT_MANIFEST_ENV_VAR: &str = "YARN_PNP_MANIFEST_BASE64";
+
+/// Loads the manifest stored as base64-encoded JSON within the
+/// `YARN_PNP_MANIFEST_BASE64` environment variable, if set.
+pub fn load_pnp_manifest_from_env() -> Result<Option<Manifest>, Error> {
+    load_pnp_manifest_from_env_var(DEFAULT_MANIFEST_ENV_VAR)
+}
+
+/// Same as `load_pnp_manifest_from_env`, but reads the given environment
+/// variable. The payload is decoded by `load_pnp_manifest_from_base64`.
+pub fn load_pnp_manifest_from_env_var(name: &str) -> Result<Option<Manifest>, Error> {
+    match read_manifest_env_var(name)? {
+        Some(encoded) => load_pnp_manifest_from_base64(&encoded).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn read_manifest_env_var(name: &str) -> Result<Option<String>, Error> {
+    match std::env::var(name) {
+        Ok(encoded) => Ok(Some(encoded)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+            message: format!("We failed to read the {name} environment variable."),
+            manifest_path: PathBuf::from("<env>"),
+            source: Some(Arc::new(err)),
+        }))),
+    }
+}
+
+/// The manifests decoded from environment variables, keyed by variable name,
+/// along with the payload they were decoded from.
+static ENV_MANIFESTS: LazyLock<RwLock<FxHashMap<String, (String, Arc<Manifest>)>>> =
+    LazyLock::new(Default::default);
+
+/// Same as `load_pnp_manifest_from_env_var`, but the payload is only decoded
+/// again when the value of the variable changed since the last call.
+fn load_pnp_manifest_from_env_var_cached(name: &str) -> Result<Option<Arc<Manifest>>, Error> {
+    match read_manifest_env_var(name)? {
+        Some(encoded) => load_env_manifest_cached(name, encoded).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn load_env_manifest_cached(name: &str, encoded: String) -> Result<Arc<Manifest>, Error> {
+    if let Some((cached_encoded, manifest)) = ENV_MANIFESTS.read().unwrap().get(name) {
+        if *cached_encoded == encoded {
+            return Ok(manifest.clone());
+        }
+    }
+
+    let manifest = Arc::new(load_pnp_manifest_from_base64(&encoded)?);
+
+    ENV_MANIFESTS.write().unwrap().insert(name.to_string(), (encoded, manifest.clone()));
+
+    Ok(manifest)
+}
+
+/// Loads a manifest from its base64-encoded JSON payload. As the manifest
+/// doesn't come from a file, its path is set to `<env>`, and the package
+/// locations are resolved relative to the current working directory.
+pub fn load_pnp_manifest_from_base64(encoded: &str) -> Result<Manifest, Error> {
+    use base64::Engine;
+
+    let manifest_path = Path::new("<env>");
+
+    let hydration_error =
+        |message: &str, source: Option<Arc<dyn std::error::Error + Send + Sync>>| {
+            Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+                message: String::from(message),
+                manifest_path: manifest_path.to_path_buf(),
+                source,
+            }))
+        };
+
+    let json_bytes =
+        base64::engine::general_purpose::STANDARD.decode(encoded.trim()).map_err(|err| {
+            hydration_error(
+                "We failed to decode the PnP data payload as base64.",
+                Some(Arc::new(err)),
+            )
+        })?;
+
+    let json_string = String::from_utf8(json_bytes).map_err(|err| {
+        hydration_error("The PnP data payload doesn't contain valid UTF-8.", Some(Arc::new(err)))
+    })?;
+
+    let mut manifest = parse_pnp_manifest_json(&json_string, manifest_path)?;
+
+    let cwd = std::env::current_dir().map_err(|err| {
+        hydration_error("We failed to read the current working directory.", Some(Arc::new(err)))
+    })?;
+
+    init_pnp_manifest(&mut manifest, &cwd.join(manifest_path));
+    manifest.manifest_path = manifest_path.to_path_buf();
+
+    Ok(manifest)
+}
+
+/// A thread-safe cache of parsed manifests, keyed by their path. Entries are
+/// invalidated whenever the modification time of the manifest file changes.
+#[derive(Debug, Default)]
+pub struct ManifestCache {
+    entries: RwLock<FxHashMap<PathBuf, (SystemTime, Arc<Manifest>)>>,
+}
+
+impl ManifestCache {
+    pub fn new() -> ManifestCache {
+        ManifestCache::default()
+    }
+
+    pub fn invalidate(&self, p: &Path) {
+        self.entries.write().unwrap().remove(p);
+    }
+}
+
+pub fn load_pnp_manifest_cached(p: &Path, cache: &ManifestCache) -> Result<Arc<Manifest>, Error> {
+    let mtime = std::fs::metadata(p).and_then(|metadata| metadata.modified()).map_err(|err| {
+        Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+            message: String::from("We failed to read the modification time of the manifest."),
+            manifest_path: p.to_path_buf(),
+            source: Some(Arc::new(err)),
+        }))
+    })?;
+
+    if let Some((cached_mtime, manifest)) = cache.entries.read().unwrap().get(p) {
+        if *cached_mtime == mtime {
+            return Ok(manifest.clone());
+        }
+    }
+
+    let manifest = Arc::new(load_pnp_manifest(p)?);
+
+    cache.entries.write().unwrap().insert(p.to_path_buf(), (mtime, manifest.clone()));
+
+    Ok(manifest)
+}
+
+/// Serializes the manifest into the JSON payload format used by Yarn within
+/// the .pnp.cjs files. The result can be parsed back into a manifest.
+pub fn serialize_pnp_json(manifest: &Manifest) -> Result<String, serde_json::Error> {
+    serde_json::to_string(manifest)
+}
+
 pub fn init_pnp_manifest(manifest: &mut Manifest, p: &Path) {
+    init_pnp_manifest_checked(manifest, p);
+}
+
+/// Same as `init_pnp_manifest`, but returns the warnings raised while
+/// normalizing the package locations (for example when they aren't valid
+/// UTF-8 and had to be converted lossily).
+pub fn init_pnp_manifest_checked(manifest: &mut Manifest, p: &Path) -> Vec<ManifestWarning> {
+    init_pnp_manifest_impl(manifest, p, &|_, _| {})
+}
+
+/// Same as `init_pnp_manifest`, but calls the `progress` callback after each
+/// package has been processed, with the number of packages processed so far
+/// and the total number of packages.
+pub fn init_pnp_manifest_with_progress(
+    manifest: &mut Manifest,
+    p: &Path,
+    progress: &dyn Fn(usize, usize),
+) {
+    init_pnp_manifest_impl(manifest, p, progress);
+}
+
+/// Same as `init_pnp_manifest`, but also opens the archives storing the
+/// zip-backed packages within the given cache, so that the first resolutions
+/// don't have to. Failing to open an archive isn't fatal; the errors are
+/// returned so they can be reported.
+pub fn init_pnp_manifest_with_zip_cache<Storage, Cache>(
+    manifest: &mut Manifest,
+    p: &Path,
+    zip_cache: &Cache,
+) -> Vec<std::io::Error>
+where
+    Storage: AsRef<[u8]> + Send + Sync,
+    Cache: fs::ZipCache<Storage> + Sync,
+{
+    init_pnp_manifest(manifest, p);
+
+    // Virtual packages share the archive of the package they instantiate
+    let mut zip_paths: Vec<PathBuf> = manifest
+        .iter_packages_with_sentinel()
+        .filter_map(|(_, info)| info.resolved_location().ok()?.into_zip_info())
+        .map(|info| info.physical_zip_path())
+        .collect();
+
+    zip_paths.sort();
+    zip_paths.dedup();
+
+    zip_cache.prefetch(zip_paths)
+}
+
+fn init_pnp_manifest_impl(
+    manifest: &mut Manifest,
+    p: &Path,
+    progress: &dyn Fn(usize, usize),
+) -> Vec<ManifestWarning> {
     manifest.manifest_path = p.to_path_buf();
 
     manifest.manifest_dir = p
@@ -158,20 +562,51 @@ pub fn init_pnp_manifest(manifest: &mut Manifest, p: &Path) {
         .unwrap_or_else(|| panic!("Should have a parent directory for path {}", p.display()))
         .to_owned();
 
-    for (name, ranges) in manifest.package_registry_data.iter_mut() {
-        for (reference, info) in ranges.iter_mut() {
-            let package_location = manifest.manifest_dir.join(info.package_location.clone());
+    let total_packages = manifest.package_count();
+    let mut processed_packages = 0;
+
+    let mut packages: Vec<_> = manifest
+        .package_registry_data
+        .iter_mut()
+        .flat_map(|(name, ranges)| {
+            ranges.iter_mut().map(move |(reference, info)| (name, reference, info))
+        })
+        .collect();
+
+    let manifest_dir = &manifest.manifest_dir;
+    let normalize_location =
+        |(name, reference, info): &mut (&String, &String, &mut PackageInformation)| {
+            let package_location = manifest_dir.join(&info.package_location);
             let normalized_location = util::normalize_path(package_location.to_string_lossy());
 
             info.package_location = PathBuf::from(normalized_location);
 
-            if !info.discard_from_lookup {
-                manifest.location_trie.insert(
-                    &info.package_location,
-                    PackageLocator { name: name.clone(), reference: reference.clone() },
-                );
-            }
+            package_location.to_str().is_none().then(|| ManifestWarning::NonUtf8PackageLocation {
+                locator: PackageLocator {
+                    name: name.to_string(),
+                    reference: reference.to_string(),
+                },
+                original_path: package_location,
+            })
+        };
+
+    // The locations are normalized upfront so that this part can run in
+    // parallel; the trie itself must be filled sequentially
+    #[cfg(feature = "parallel")]
+    let warnings: Vec<_> = packages.par_iter_mut().filter_map(normalize_location).collect();
+    #[cfg(not(feature = "parallel"))]
+    let warnings: Vec<_> = packages.iter_mut().filter_map(normalize_location).collect();
+
+    for (name, reference, info) in packages {
+        if !info.discard_from_lookup {
+            let locator = PackageLocator { name: name.clone(), reference: reference.clone() };
+
+            manifest.location_map.insert(info.package_location.clone(), locator.clone());
+            manifest.location_trie.insert(&info.package_location, locator);
         }
+
+        processed_packages += 1;
+        progress(processed_packages, total_packages);
     }
 
     let top_level_pkg = manifest
@@ -186,54 +621,153 @@ pub fn init_pnp_manifest(manifest: &mut Manifest, p: &Path) {
             entry.insert(dependency.clone());
         }
     }
+
+    warnings
 }
 
+/// Returns the manifest stored within the `YARN_PNP_MANIFEST_BASE64`
+/// environment variable if set, or else the closest manifest found on disk.
 pub fn find_pnp_manifest(parent: &Path) -> Result<Option<Manifest>, Error> {
-    find_closest_pnp_manifest_path(parent).map_or(Ok(None), |p| Ok(Some(load_pnp_manifest(&p)?)))
+    if let Some(manifest) = load_pnp_manifest_from_env_var_cached(DEFAULT_MANIFEST_ENV_VAR)? {
+        return Ok(Some(Manifest::clone(&manifest)));
+    }
+
+    find_pnp_manifest_named(parent, DEFAULT_MANIFEST_FILENAMES)
+}
+
+pub fn find_pnp_manifest_named(
+    parent: &Path,
+    filenames: &[&str],
+) -> Result<Option<Manifest>, Error> {
+    find_closest_pnp_manifest_path_named(parent, filenames)
+        .map_or(Ok(None), |p| Ok(Some(load_pnp_manifest(&p)?)))
+}
+
+/// Finds all the PnP manifests stored within the given directory tree, and
+/// lazily loads them in path order. The `node_modules` and `.yarn` folders
+/// aren't traversed, as they can't contain project manifests.
+pub fn find_all_pnp_manifests_in_tree(
+    root: &Path,
+) -> impl Iterator<Item = Result<Manifest, Error>> {
+    let mut manifest_paths = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(dir_entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for dir_entry in dir_entries.flatten() {
+            let path = dir_entry.path();
+            let file_name = dir_entry.file_name();
+
+            if dir_entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                if file_name != "node_modules" && file_name != ".git" && file_name != ".yarn" {
+                    pending.push(path);
+                }
+            } else if DEFAULT_MANIFEST_FILENAMES.iter().any(|filename| file_name == *filename) {
+                manifest_paths.push(path);
+            }
+        }
+    }
+
+    manifest_paths.sort();
+    manifest_paths.into_iter().map(|p| load_pnp_manifest(&p))
 }
 
 pub fn is_dependency_tree_root<'a>(manifest: &'a Manifest, locator: &'a PackageLocator) -> bool {
     manifest.dependency_tree_roots.contains(locator)
 }
 
-pub fn find_locator<'a>(manifest: &'a Manifest, path: &Path) -> Option<&'a PackageLocator> {
-    let rel_path = pathdiff::diff_paths(path, &manifest.manifest_dir).unwrap_or_else(|| {
-        panic!("Assertion failed: Provided path should be absolute but received {}", path.display())
-    });
+/// Options for `find_locator_with_options`.
+pub struct FindLocatorOptions {
+    /// Whether virtual packages (whose reference starts with `virtual:`)
+    /// may be returned. If unset, the closest non-virtual ancestor package
+    /// is returned instead.
+    pub include_virtual: bool,
+}
 
-    if let Some(regex) = &manifest.ignore_pattern_data {
-        if regex.0.is_match(&util::normalize_path(rel_path.to_string_lossy())).unwrap() {
-            return None;
-        }
+impl Default for FindLocatorOptions {
+    fn default() -> Self {
+        Self { include_virtual: true }
     }
+}
 
-    let path = util::normalize_path(path.to_string_lossy());
+/// Returns the locator of the package containing the given path, along with
+/// the path of the package root.
+///
+/// The path must be absolute and may point to either a file or a directory;
+/// a trailing slash isn't required for directories, as any path is matched
+/// against the package locations on a per-segment basis.
+pub fn find_locator<'a>(
+    manifest: &'a Manifest,
+    path: &Path,
+) -> Option<(&'a PackageLocator, &'a Path)> {
+    find_locator_with_options(manifest, path, &FindLocatorOptions::default())
+}
+
+pub fn find_locator_with_options<'a>(
+    manifest: &'a Manifest,
+    path: &Path,
+    options: &FindLocatorOptions,
+) -> Option<(&'a PackageLocator, &'a Path)> {
+    if manifest.is_ignored_path(path) {
+        return None;
+    }
 
-    manifest.location_trie.get_ancestor_value(&path)
+    // The trie takes care of normalizing the path
+    let (mut root, mut locator) = manifest.location_trie.get_ancestor_entry(&path)?;
+
+    while !options.include_virtual && locator.is_virtual() {
+        (root, locator) = manifest.location_trie.get_ancestor_entry(&root.parent()?)?;
+    }
+
+    Some((locator, root))
 }
 
 pub fn get_package<'a>(
     manifest: &'a Manifest,
     locator: &PackageLocator,
 ) -> Result<&'a PackageInformation, Error> {
-    let references = manifest.package_registry_data.get(&locator.name).unwrap_or_else(|| {
-        panic!("Should have an entry in the package registry for {}", locator.name)
-    });
+    get_package_by_name(manifest, &locator.name, &locator.reference)
+}
+
+/// Returns the package containing the given path, along with its locator.
+/// Returns `None` if the path doesn't belong to any package.
+pub fn get_package_at_path<'a>(
+    manifest: &'a Manifest,
+    path: &Path,
+) -> Option<Result<(&'a PackageLocator, &'a PackageInformation), Error>> {
+    let (locator, _) = find_locator(manifest, path)?;
+
+    Some(get_package(manifest, locator).map(|info| (locator, info)))
+}
+
+fn get_package_by_name<'a>(
+    manifest: &'a Manifest,
+    name: &str,
+    reference: &str,
+) -> Result<&'a PackageInformation, Error> {
+    let references = manifest
+        .package_registry_data
+        .get(name)
+        .unwrap_or_else(|| panic!("Should have an entry in the package registry for {name}"));
 
-    let info = references.get(&locator.reference).unwrap_or_else(|| {
-        panic!("Should have an entry in the package registry for {}", locator.reference)
-    });
+    let info = references
+        .get(reference)
+        .unwrap_or_else(|| panic!("Should have an entry in the package registry for {reference}"));
 
     Ok(info)
 }
 
+#[deprecated(note = "Use `Manifest::is_excluded_from_fallback` instead")]
 pub fn is_excluded_from_fallback(manifest: &Manifest, locator: &PackageLocator) -> bool {
-    manifest
-        .fallback_exclusion_list
-        .get(&locator.name)
-        .is_some_and(|references| references.contains(&locator.reference))
+    manifest.is_excluded_from_fallback(locator)
 }
 
+#[deprecated(
+    note = "Use `Manifest::find_broken_peer_dependencies` instead, which has access to the dependency graph"
+)]
 pub fn find_broken_peer_dependencies(
     _dependency: &str,
     _initial_package: &PackageLocator,
@@ -246,28 +780,82 @@ pub fn resolve_to_unqualified_via_manifest(
     specifier: &str,
     parent: &Path,
 ) -> Result<Resolution, Error> {
-    let (ident, module_path) = parse_bare_identifier(specifier)?;
+    resolve_to_unqualified_via_manifest_impl(manifest, specifier, parent, None)
+}
+
+/// Same as `resolve_to_unqualified_via_manifest`, but also calls the
+/// `on_fallback` callback of the configuration when relevant. The host of the
+/// configuration isn't used, since the manifest is already known.
+pub fn resolve_to_unqualified_via_manifest_with_config(
+    manifest: &Manifest,
+    specifier: &str,
+    parent: &Path,
+    config: &ResolutionConfig,
+) -> Result<Resolution, Error> {
+    resolve_to_unqualified_via_manifest_impl(
+        manifest,
+        specifier,
+        parent,
+        config.on_fallback.as_deref(),
+    )
+}
+
+fn resolve_to_unqualified_via_manifest_impl(
+    manifest: &Manifest,
+    specifier: &str,
+    parent: &Path,
+    on_fallback: Option<&FallbackCallback>,
+) -> Result<Resolution, Error> {
+    let (ident, module_path) = parse_bare_identifier_ref(specifier)?;
+
+    if let Some((parent_locator, _)) = find_locator(manifest, parent) {
+        debug_event!(
+            name = %parent_locator.name,
+            reference = %parent_locator.reference,
+            "found locator"
+        );
 
-    if let Some(parent_locator) = find_locator(manifest, parent) {
         let parent_pkg = get_package(manifest, parent_locator)?;
 
-        let mut reference_or_alias: Option<PackageDependency> = None;
+        debug_event!(dependency = ident, "looking up dependency");
+
+        let mut reference_or_alias: Option<&PackageDependency> = None;
         let mut is_set = false;
 
         if !is_set {
-            if let Some(Some(binding)) = parent_pkg.package_dependencies.get(&ident) {
-                reference_or_alias = Some(binding.clone());
+            // Unfulfilled peer dependencies are set to null; they may still be
+            // provided by the fallback, but are otherwise missing peers
+            if let Some(binding) = parent_pkg.package_dependencies.get(ident) {
+                reference_or_alias = binding.as_ref();
                 is_set = true;
             }
         }
 
+        // Workspaces may import themselves by name even when they don't list
+        // themselves in their dependencies
         if !is_set
+            && ident == parent_locator.name
+            && is_dependency_tree_root(manifest, parent_locator)
+        {
+            return Ok(Resolution::Resolved(
+                parent_pkg.package_location.clone(),
+                module_path.map(|v| v.to_string()),
+            ));
+        }
+
+        if reference_or_alias.is_none()
             && manifest.enable_top_level_fallback
-            && !is_excluded_from_fallback(manifest, parent_locator)
+            && !manifest.is_excluded_from_fallback(parent_locator)
         {
-            if let Some(fallback_resolution) = manifest.fallback_pool.get(&ident) {
-                reference_or_alias = fallback_resolution.clone();
+            if let Some(fallback_resolution) = manifest.fallback_pool.get(ident) {
+                debug_event!(dependency = ident, "using fallback pool");
+
+                reference_or_alias = fallback_resolution.as_ref();
                 is_set = true;
+
+                if let (Some(on_fallback), Some(_)) = (on_fallback, reference_or_alias) {
+                    on_fallback(ident, parent_locator);
+                }
             }
         }
 
@@ -326,7 +914,7 @@ pub fn resolve_to_unqualified_via_manifest(
             return Err(Error::UndeclaredDependency(Box::new(UndeclaredDependency {
                 message,
                 request: specifier.to_string(),
-                dependency_name: ident,
+                dependency_name: ident.to_string(),
                 issuer_locator: parent_locator.clone(),
                 issuer_path: parent.to_path_buf(),
             })));
@@ -335,16 +923,19 @@ pub fn resolve_to_unqualified_via_manifest(
         if let Some(resolution) = reference_or_alias {
             let dependency_pkg = match resolution {
                 PackageDependency::Reference(reference) => {
-                    get_package(manifest, &PackageLocator { name: ident, reference })
+                    get_package_by_name(manifest, ident, reference)
                 }
                 PackageDependency::Alias(name, reference) => {
-                    get_package(manifest, &PackageLocator { name, reference })
+                    get_package_by_name(manifest, name, reference)
                 }
             }?;
 
-            Ok(Resolution::Resolved(dependency_pkg.package_location.clone(), module_path))
+            Ok(Resolution::Resolved(
+                dependency_pkg.package_location.clone(),
+                module_path.map(|v| v.to_string()),
+            ))
         } else {
-            let broken_ancestors = find_broken_peer_dependencies(specifier, parent_locator);
+            let broken_ancestors = manifest.find_broken_peer_dependencies(ident, parent_locator);
 
             let message = if is_dependency_tree_root(manifest, parent_locator) {
                 format!(
@@ -390,13 +981,15 @@ pub fn resolve_to_unqualified_via_manifest(
             Err(Error::MissingPeerDependency(Box::new(MissingPeerDependency {
                 message,
                 request: specifier.to_string(),
-                dependency_name: ident,
+                dependency_name: ident.to_string(),
                 issuer_locator: parent_locator.clone(),
                 issuer_path: parent.to_path_buf(),
-                broken_ancestors: Vec::new(),
+                broken_ancestors,
             })))
         }
     } else {
+        debug_event!(parent = %parent.display(), "resolution skipped");
+
         Ok(Resolution::Skipped)
     }
 }
@@ -406,10 +999,204 @@ pub fn resolve_to_unqualified(
     parent: &Path,
     config: &ResolutionConfig,
 ) -> Result<Resolution, Error> {
-    if let Some(manifest) = (config.host.find_pnp_manifest)(parent)? {
-        resolve_to_unqualified_via_manifest(&manifest, specifier, parent)
-    } else {
-        Ok(Resolution::Skipped)
+    let env_manifest = match &config.env_manifest_var {
+        Some(name) => load_pnp_manifest_from_env_var_cached(name)?,
+        None => None,
+    };
+
+    let host_manifest;
+    let manifest = match &env_manifest {
+        Some(manifest) => Some(manifest.as_ref()),
+        None => {
+            host_manifest = (config.host.find_pnp_manifest)(parent)?;
+            host_manifest.as_ref()
+        }
+    };
+
+    let Some(manifest) = manifest else {
+        debug_event!(parent = %parent.display(), "resolution skipped");
+
+        if config.strict {
+            return Err(Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+                message: format!(
+                    "No PnP manifest found to resolve {specifier} from {}; this isn't allowed in strict mode.",
+                    parent.display()
+                ),
+                manifest_path: parent.to_path_buf(),
+                source: None,
+            })));
+        }
+
+        return Ok(Resolution::Skipped);
+    };
+
+    let resolution =
+        resolve_to_unqualified_via_manifest_with_config(manifest, specifier, parent, config)?;
+
+    if config.strict && matches!(resolution, Resolution::Skipped) {
+        let message = if manifest.is_ignored_path(parent) {
+            format!(
+                "The PnP manifest ignores {}, so {specifier} can't be resolved from there; this isn't allowed in strict mode.",
+                parent.display()
+            )
+        } else {
+            format!(
+                "The PnP manifest doesn't list any package containing {}, so {specifier} can't be resolved from there; this isn't allowed in strict mode.",
+                parent.display()
+            )
+        };
+
+        return Err(Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+            message,
+            manifest_path: manifest.manifest_path.clone(),
+            source: None,
+        })));
+    }
+
+    Ok(resolution)
+}
+
+impl Manifest {
+    pub fn is_dependency_tree_root(&self, locator: &PackageLocator) -> bool {
+        is_dependency_tree_root(self, locator)
+    }
+
+    pub fn find_locator(&self, path: &Path) -> Option<(&PackageLocator, &Path)> {
+        find_locator(self, path)
+    }
+
+    pub fn get_package(&self, locator: &PackageLocator) -> Result<&PackageInformation, Error> {
+        get_package(self, locator)
+    }
+
+    /// Returns the ancestors that should have provided the given peer
+    /// dependency to the package but didn't. The ancestors are followed along
+    /// the dependency path from the closest dependency tree root, going up as
+    /// long as they also list the dependency as one of their peers.
+    pub fn find_broken_peer_dependencies(
+        &self,
+        dependency: &str,
+        initial_package: &PackageLocator,
+    ) -> Vec<PackageLocator> {
+        let Some(mut path) = self.dependency_path_to(initial_package) else {
+            return Vec::new();
+        };
+
+        // The package itself is the last item of the path
+        path.pop();
+
+        while let Some(ancestor) = path.pop() {
+            let forwards_peer = self.get_package_information(&ancestor).is_some_and(|info| {
+                info.package_peers.contains(dependency)
+                    && info.package_dependencies.get(dependency).is_some_and(Option::is_none)
+            });
+
+            if !forwards_peer {
+                return vec![ancestor];
+            }
+        }
+
+        Vec::new()
+    }
+
+    pub fn resolve_to_unqualified(
+        &self,
+        specifier: &str,
+        parent: &Path,
+    ) -> Result<Resolution, Error> {
+        resolve_to_unqualified_via_manifest(self, specifier, parent)
+    }
+
+    /// Returns the shortest chain of dependencies leading from a dependency
+    /// tree root to the given package, both included. Returns `None` if the
+    /// package can't be reached from any root.
+    pub fn dependency_path_to(&self, target: &PackageLocator) -> Option<Vec<PackageLocator>> {
+        let mut parents: FxHashMap<PackageLocator, Option<PackageLocator>> =
+            self.dependency_tree_roots.iter().map(|root| (root.clone(), None)).collect();
+        let mut queue: VecDeque<PackageLocator> =
+            self.dependency_tree_roots.iter().cloned().collect();
+
+        while let Some(locator) = queue.pop_front() {
+            if &locator == target {
+                let mut path = vec![locator];
+
+                while let Some(Some(parent)) = parents.get(path.last().unwrap()) {
+                    path.push(parent.clone());
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            let Some(info) = self.get_package_information(&locator) else {
+                continue;
+            };
+
+            for (name, dependency) in &info.package_dependencies {
+                let Some(dependency) = dependency else {
+                    continue;
+                };
+
+                let dependency = dependency.to_locator(name);
+
+                if let std::collections::hash_map::Entry::Vacant(entry) =
+                    parents.entry(dependency.clone())
+                {
+                    entry.insert(Some(locator.clone()));
+                    queue.push_back(dependency);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns all the packages the given package depends on, directly or
+    /// not, up to the given depth (direct dependencies being at depth 1).
+    /// Dependency cycles are valid and each package is only visited once;
+    /// the root package itself is never part of the result.
+    pub fn transitive_dependencies_of(
+        &self,
+        root: &PackageLocator,
+        max_depth: Option<usize>,
+    ) -> Result<FxHashSet<PackageLocator>, Error> {
+        let mut dependencies = FxHashSet::default();
+        let mut queue = VecDeque::from([(root.clone(), 0)]);
+
+        while let Some((locator, depth)) = queue.pop_front() {
+            if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                continue;
+            }
+
+            let info = self
+                .package_registry_data
+                .get(&locator.name)
+                .and_then(|references| references.get(&locator.reference))
+                .ok_or_else(|| {
+                    Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+                        message: format!(
+                            "The package registry doesn't contain {}@{}, but it's listed as a dependency.",
+                            locator.name, locator.reference
+                        ),
+                        manifest_path: self.manifest_path.clone(),
+                        source: None,
+                    }))
+                })?;
+
+            for (name, dependency) in &info.package_dependencies {
+                let Some(dependency) = dependency else {
+                    continue;
+                };
+
+                let dependency = dependency.to_locator(name);
+
+                if &dependency != root && dependencies.insert(dependency.clone()) {
+                    queue.push_back((dependency, depth + 1));
+                }
+            }
+        }
+
+        Ok(dependencies)
     }
 }
 
@@ -20,22 +20,82 @@ struct TestSuite {
 
 #[cfg(test)]
 mod tests {
-    use std::{env, fs, path::PathBuf};
+    use std::{
+        cell::RefCell,
+        env, fs,
+        hash::{Hash, Hasher},
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    use rstest::rstest;
+    use rustc_hash::{FxHashSet, FxHasher};
 
     use super::*;
     use crate::{
-        ResolutionConfig, ResolutionHost, init_pnp_manifest, load_pnp_manifest,
-        parse_bare_identifier, resolve_to_unqualified, resolve_to_unqualified_via_manifest, util,
+        BareIdentifier, Error, FallbackResolutionHost, FindLocatorOptions, ManifestCache,
+        ManifestDiff, ManifestWarning, PackageDependency, PackageInformation,
+        PackageInformationBuilder, PackageLocator, ResolutionConfig, ResolutionHost,
+        find_all_pnp_manifests_in_tree, find_closest_pnp_manifest_path_named, find_locator,
+        find_locator_with_options, fs::VPath, get_package_at_path, init_pnp_manifest,
+        init_pnp_manifest_checked, init_pnp_manifest_with_progress,
+        init_pnp_manifest_with_zip_cache, load_pnp_manifest, load_pnp_manifest_cached,
+        load_pnp_manifest_from_base64, load_pnp_manifest_from_env_var, parse_bare_identifier,
+        resolve_to_unqualified, resolve_to_unqualified_via_manifest,
+        resolve_to_unqualified_via_manifest_with_config, serialize_pnp_json, util,
     };
 
+    /// Loads the suites from `data/test-expectations.json`, with their
+    /// manifests initialized as if they were stored at
+    /// `/path/to/project/.pnp.cjs`.
+    fn load_test_suites() -> Vec<TestSuite> {
+        let expectations_path = env::current_dir()
+            .expect("Assertion failed: Expected a valid current working directory")
+            .join("data/test-expectations.json");
+
+        let manifest_content = fs::read_to_string(&expectations_path)
+            .expect("Assertion failed: Expected the expectations to be found");
+
+        let mut test_suites: Vec<TestSuite> = serde_json::from_str(&manifest_content)
+            .expect("Assertion failed: Expected the expectations to be loaded");
+
+        for test_suite in test_suites.iter_mut() {
+            init_pnp_manifest(&mut test_suite.manifest, Path::new("/path/to/project/.pnp.cjs"));
+        }
+
+        test_suites
+    }
+
+    /// Parses a manifest holding the given `packageRegistryData` entries,
+    /// with `app@workspace:.` as its dependency tree root.
+    fn parse_manifest(package_registry_data: serde_json::Value) -> Manifest {
+        serde_json::from_value(serde_json::json!({
+            "enableTopLevelFallback": false,
+            "ignorePatternData": null,
+            "dependencyTreeRoots": [{"name": "app", "reference": "workspace:."}],
+            "fallbackPool": [],
+            "fallbackExclusionList": [],
+            "packageRegistryData": package_registry_data,
+        }))
+        .expect("Assertion failed: Expected the manifest to be parsed")
+    }
+
+    /// Same as `parse_manifest`, with the manifest initialized as if it were
+    /// stored at `/path/to/project/.pnp.cjs`.
+    fn manifest_with_registry(package_registry_data: serde_json::Value) -> Manifest {
+        let mut manifest = parse_manifest(package_registry_data);
+        init_pnp_manifest(&mut manifest, Path::new("/path/to/project/.pnp.cjs"));
+        manifest
+    }
+
     #[test]
     fn example() {
         let manifest = load_pnp_manifest(Path::new("data/pnp-yarn-v3.cjs")).unwrap();
 
         let host =
-            ResolutionHost { find_pnp_manifest: Box::new(move |_| Ok(Some(manifest.clone()))) };
+            ResolutionHost { find_pnp_manifest: Arc::new(move |_| Ok(Some(manifest.clone()))) };
 
-        let config = ResolutionConfig { host };
+        let config = ResolutionConfig { host, ..Default::default() };
 
         let resolution =
             resolve_to_unqualified("lodash/cloneDeep", Path::new("/path/to/file"), &config);
@@ -57,6 +117,27 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_resolution_config_clone() {
+        let manifest = load_pnp_manifest(Path::new("data/pnp-yarn-v4.cjs")).unwrap();
+        let issuer = manifest.manifest_dir.join("index.js");
+
+        let host =
+            ResolutionHost { find_pnp_manifest: Arc::new(move |_| Ok(Some(manifest.clone()))) };
+
+        let config = ResolutionConfig { host, strict: true, ..Default::default() };
+        let cloned_config = config.clone();
+
+        let resolution = std::thread::spawn(move || {
+            resolve_to_unqualified("not-a-dependency", &issuer, &cloned_config)
+        })
+        .join()
+        .unwrap();
+
+        assert!(config.strict);
+        assert!(matches!(resolution, Err(Error::UndeclaredDependency(_))));
+    }
+
     #[test]
     fn test_load_pnp_manifest() {
         load_pnp_manifest(Path::new("data/pnp-yarn-v3.cjs"))
@@ -66,6 +147,349 @@ mod tests {
             .expect("Assertion failed: Expected to load the .pnp.cjs file generated by Yarn 4");
     }
 
+    #[test]
+    fn test_error_into_io_error() {
+        fn load(p: &Path) -> std::io::Result<Manifest> {
+            Ok(load_pnp_manifest(p)?)
+        }
+
+        let err = load(Path::new("data/missing.cjs")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<Error>(),
+            Some(Error::FailedManifestHydration(_))
+        ));
+
+        let source = std::error::Error::source(&err)
+            .expect("Assertion failed: Expected the source chain to be preserved");
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_load_pnp_manifest_unsupported_version() {
+        let err = load_pnp_manifest(Path::new("data/pnp-future-version.cjs")).unwrap_err();
+
+        match err {
+            Error::FailedManifestHydration(failure) => {
+                assert!(failure.message.contains("unsupported manifest version (99)"));
+            }
+            _ => {
+                panic!("Expected a manifest hydration failure");
+            }
+        }
+
+        let manifest = load_pnp_manifest(Path::new("data/pnp-yarn-v4.cjs")).unwrap();
+        assert_eq!(manifest.metadata, None);
+    }
+
+    // The environment is never modified by the tests, as doing so while other
+    // threads read it is undefined behaviour; the payload decoding is tested
+    // through `load_pnp_manifest_from_base64` instead
+    #[test]
+    fn test_load_pnp_manifest_from_base64() {
+        use base64::Engine;
+
+        let manifest = load_pnp_manifest(Path::new("data/pnp-yarn-v3.cjs")).unwrap();
+        let encoded = base64::engine::general_purpose::STANDARD
+            .encode(serialize_pnp_json(&manifest).unwrap());
+
+        let decoded_manifest = load_pnp_manifest_from_base64(&encoded).unwrap();
+
+        assert_eq!(decoded_manifest.package_count(), manifest.package_count());
+        assert_eq!(decoded_manifest.dependency_tree_roots, manifest.dependency_tree_roots);
+        assert_eq!(decoded_manifest.manifest_path, Path::new("<env>"));
+        assert_eq!(decoded_manifest.manifest_dir, env::current_dir().unwrap());
+
+        match load_pnp_manifest_from_base64("not base64!").unwrap_err() {
+            Error::FailedManifestHydration(failure) => {
+                assert_eq!(failure.manifest_path, Path::new("<env>"));
+                assert!(failure.message.contains("base64"), "{}", failure.message);
+            }
+            err => panic!("Expected a manifest hydration failure, got {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_load_env_manifest_cached() {
+        use base64::Engine;
+
+        let encode = |path: &str| {
+            let manifest = load_pnp_manifest(Path::new(path)).unwrap();
+            base64::engine::general_purpose::STANDARD.encode(serialize_pnp_json(&manifest).unwrap())
+        };
+
+        let var = "PNP_RS_TEST_CACHED_MANIFEST_VAR";
+        let v3 = encode("data/pnp-yarn-v3.cjs");
+        let v4 = encode("data/pnp-yarn-v4.cjs");
+
+        let first = crate::load_env_manifest_cached(var, v3.clone()).unwrap();
+        let second = crate::load_env_manifest_cached(var, v3).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // A new payload gets decoded again
+        let third = crate::load_env_manifest_cached(var, v4).unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+    }
+
+    #[test]
+    fn test_resolve_with_env_manifest_var() {
+        let var = "PNP_RS_TEST_UNSET_MANIFEST_VAR";
+        assert!(load_pnp_manifest_from_env_var(var).unwrap().is_none());
+
+        let host_calls = Arc::new(Mutex::new(0));
+        let host_calls_clone = host_calls.clone();
+
+        let host = ResolutionHost {
+            find_pnp_manifest: Arc::new(move |_| {
+                *host_calls_clone.lock().unwrap() += 1;
+                Ok(None)
+            }),
+        };
+        let config = ResolutionConfig {
+            host,
+            env_manifest_var: Some(var.to_string()),
+            ..Default::default()
+        };
+
+        // The host is queried when the variable isn't set
+        let issuer = env::current_dir().unwrap().join("index.js");
+        assert!(matches!(
+            resolve_to_unqualified("left-pad", &issuer, &config),
+            Ok(Resolution::Skipped)
+        ));
+        assert_eq!(*host_calls.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_load_pnp_manifest_cached() {
+        let cache = ManifestCache::new();
+        let manifest_path = Path::new("data/pnp-yarn-v3.cjs");
+
+        let first = load_pnp_manifest_cached(manifest_path, &cache).unwrap();
+        let second = load_pnp_manifest_cached(manifest_path, &cache).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        cache.invalidate(manifest_path);
+
+        let third = load_pnp_manifest_cached(manifest_path, &cache).unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+
+        assert!(load_pnp_manifest_cached(Path::new("data/missing.cjs"), &cache).is_err());
+    }
+
+    #[test]
+    fn test_load_pnp_manifest_cached_reloads_on_mtime_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manifest_path = temp_dir.path().join(".pnp.cjs");
+
+        fs::copy("data/pnp-yarn-v3.cjs", &manifest_path).unwrap();
+
+        let cache = ManifestCache::new();
+        let first = load_pnp_manifest_cached(&manifest_path, &cache).unwrap();
+
+        // The modification time is set explicitly, as the file system may not
+        // be precise enough to tell both writes apart
+        let mtime = fs::metadata(&manifest_path).unwrap().modified().unwrap();
+        fs::copy("data/pnp-yarn-v4-registry-data-order.cjs", &manifest_path).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&manifest_path)
+            .unwrap()
+            .set_modified(mtime + std::time::Duration::from_secs(10))
+            .unwrap();
+
+        let second = load_pnp_manifest_cached(&manifest_path, &cache).unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_ne!(first.package_count(), second.package_count());
+
+        let third = load_pnp_manifest_cached(&manifest_path, &cache).unwrap();
+        assert!(Arc::ptr_eq(&second, &third));
+    }
+
+    #[test]
+    fn test_load_pnp_manifest_error_source() {
+        let err = load_pnp_manifest(Path::new("data/missing.cjs")).unwrap_err();
+
+        match &err {
+            Error::FailedManifestHydration(failure) => {
+                let source = std::error::Error::source(failure.as_ref())
+                    .expect("Assertion failed: Expected the IO error to be exposed");
+
+                assert!(source.downcast_ref::<std::io::Error>().is_some());
+            }
+            _ => {
+                panic!("Expected a manifest hydration failure");
+            }
+        }
+    }
+
+    #[test]
+    fn test_package_registry_data_error_context() {
+        let json = r#"{
+            "enableTopLevelFallback": false,
+            "ignorePatternData": null,
+            "dependencyTreeRoots": [],
+            "fallbackPool": [],
+            "fallbackExclusionList": [],
+            "packageRegistryData": [
+                [null, [[null, {"packageLocation": "./", "packageDependencies": []}]]],
+                ["left-pad", [["npm:1.3.0", {"packageLocation": null, "packageDependencies": []}]]]
+            ]
+        }"#;
+
+        let err = serde_json::from_str::<Manifest>(json).unwrap_err();
+
+        assert!(
+            err.to_string()
+                .starts_with("Failed to deserialize package registry entry for `left-pad`: "),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_serialize_resolution() {
+        let resolution = Resolution::Resolved(PathBuf::from("/path/to/lodash/"), None);
+        let json = serde_json::to_value(&resolution).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({"type": "resolved", "path": "/path/to/lodash/", "subpath": null})
+        );
+
+        let resolution: Resolution = serde_json::from_value(serde_json::json!({
+            "type": "resolved",
+            "path": "/path/to/lodash/",
+            "subpath": "fp",
+        }))
+        .unwrap();
+
+        match resolution {
+            Resolution::Resolved(path, subpath) => {
+                assert_eq!(path, PathBuf::from("/path/to/lodash/"));
+                assert_eq!(subpath.as_deref(), Some("fp"));
+            }
+            Resolution::Skipped => panic!("Unexpected skipped resolution"),
+        }
+
+        let json = serde_json::to_value(Resolution::Skipped).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "skipped"}));
+        assert!(matches!(serde_json::from_value(json).unwrap(), Resolution::Skipped));
+    }
+
+    #[test]
+    fn test_serialize_package_information() {
+        let manifest =
+            load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+
+        for (_, info) in manifest.iter_packages_with_sentinel() {
+            let json = serde_json::to_string(info).unwrap();
+            let reloaded: PackageInformation = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(&reloaded, info);
+        }
+    }
+
+    #[test]
+    fn test_serialize_pnp_json() {
+        let manifest_path = std::env::current_dir().unwrap().join("data/pnp-yarn-v4.cjs");
+        let manifest = load_pnp_manifest(&manifest_path).unwrap();
+
+        let json = serialize_pnp_json(&manifest).unwrap();
+        assert!(json.contains("\"packageRegistryData\":[[null,[[null,{\"packageLocation\":\"./\""));
+
+        let mut reloaded: Manifest = serde_json::from_str(&json).unwrap();
+        init_pnp_manifest(&mut reloaded, &manifest_path);
+
+        assert_eq!(
+            reloaded.iter_packages_with_sentinel().collect::<Vec<_>>().len(),
+            manifest.package_count()
+        );
+
+        for ((locator, info), (reloaded_locator, reloaded_info)) in
+            manifest.iter_packages_with_sentinel().zip(reloaded.iter_packages_with_sentinel())
+        {
+            assert_eq!(locator, reloaded_locator);
+            assert_eq!(info.package_location, reloaded_info.package_location);
+            assert_eq!(info.package_dependencies.len(), reloaded_info.package_dependencies.len());
+        }
+
+        assert_eq!(manifest.dependency_tree_roots, reloaded.dependency_tree_roots);
+        assert_eq!(manifest.fallback_pool.len(), reloaded.fallback_pool.len());
+    }
+
+    #[test]
+    fn test_manifest_eq() {
+        let manifest_path = std::env::current_dir().unwrap().join("data/pnp-yarn-v3.cjs");
+        let manifest = load_pnp_manifest(&manifest_path).unwrap();
+
+        let json = serialize_pnp_json(&manifest).unwrap();
+
+        // The location is derived and thus doesn't take part in the comparison
+        let mut reloaded: Manifest = serde_json::from_str(&json).unwrap();
+        init_pnp_manifest(&mut reloaded, &manifest_path);
+        assert_eq!(manifest, reloaded);
+
+        // Neither is the project location, as long as the packages stay at the
+        // same place relative to the manifest
+        let mut moved: Manifest = serde_json::from_str(&json).unwrap();
+        init_pnp_manifest(&mut moved, Path::new("/elsewhere/project/.pnp.cjs"));
+        assert_eq!(manifest, moved);
+
+        reloaded.enable_top_level_fallback = !reloaded.enable_top_level_fallback;
+        assert_ne!(manifest, reloaded);
+
+        let other = load_pnp_manifest(Path::new("data/pnp-yarn-v4.cjs")).unwrap();
+        assert_ne!(manifest, other);
+    }
+
+    #[test]
+    fn test_manifest_sort() {
+        let expectations_path =
+            std::env::current_dir().unwrap().join("data/test-expectations.json");
+        let manifest_content = fs::read_to_string(&expectations_path).unwrap();
+
+        let test_suites: Vec<serde_json::Value> = serde_json::from_str(&manifest_content).unwrap();
+        let mut manifest_json = test_suites[1]["manifest"].clone();
+
+        manifest_json["packageRegistryData"].as_array_mut().unwrap().push(serde_json::json!(
+            ["peer-consumer", [["npm:1.0.0", {
+                "packageLocation": "./peer-consumer/",
+                "packageDependencies": [["test-1", null], ["test-2", null]],
+                "packagePeers": ["test-2", "test-1"],
+            }]]]
+        ));
+
+        // Build a second manifest with the very same content, but listed in reverse order
+        let mut reversed_json = manifest_json.clone();
+        for key in ["dependencyTreeRoots", "fallbackPool", "packageRegistryData"] {
+            reversed_json[key].as_array_mut().unwrap().reverse();
+        }
+        for entry in reversed_json["packageRegistryData"].as_array_mut().unwrap() {
+            for reference in entry[1].as_array_mut().unwrap() {
+                reference[1]["packageDependencies"].as_array_mut().unwrap().reverse();
+                if let Some(peers) = reference[1].get_mut("packagePeers") {
+                    peers.as_array_mut().unwrap().reverse();
+                }
+            }
+        }
+
+        let mut manifest: Manifest = serde_json::from_value(manifest_json).unwrap();
+        let mut reversed: Manifest = serde_json::from_value(reversed_json).unwrap();
+
+        assert_ne!(serialize_pnp_json(&manifest).unwrap(), serialize_pnp_json(&reversed).unwrap());
+
+        manifest.sort();
+        reversed.sort();
+
+        assert_eq!(serialize_pnp_json(&manifest).unwrap(), serialize_pnp_json(&reversed).unwrap());
+
+        let peers = &manifest.package_registry_data["peer-consumer"]["npm:1.0.0"].package_peers;
+        assert_eq!(peers.iter().collect::<Vec<_>>(), ["test-1", "test-2"]);
+    }
+
     #[test]
     fn test_resolve_unqualified() {
         let expectations_path = std::env::current_dir()
@@ -89,10 +513,10 @@ mod tests {
                 let manifest_copy = manifest.clone();
 
                 let host = ResolutionHost {
-                    find_pnp_manifest: Box::new(move |_| Ok(Some(manifest_copy.clone()))),
+                    find_pnp_manifest: Arc::new(move |_| Ok(Some(manifest_copy.clone()))),
                 };
 
-                let config = ResolutionConfig { host };
+                let config = ResolutionConfig { host, ..Default::default() };
 
                 let resolution = resolve_to_unqualified(specifier, parent, &config);
 
@@ -112,55 +536,1374 @@ mod tests {
     }
 
     #[test]
-    fn test_edge_case_one_pkg_cached_and_unplugged() {
-        let manifest = {
-            let manifest_json_path =
-                std::env::current_dir().unwrap().join("./data/edge_case_manifest_state.json");
-            let manifest_content = fs::read_to_string(&manifest_json_path).unwrap();
-            let mut manifest = serde_json::from_str::<Manifest>(&manifest_content).unwrap();
-            init_pnp_manifest(&mut manifest, &manifest_json_path);
-            manifest
+    fn test_manifest_statistics() {
+        let test_suites = load_test_suites();
+
+        let manifest = &test_suites[0].manifest;
+        assert_eq!(manifest.package_count(), 11);
+        assert_eq!(manifest.alias_count(), 1);
+        assert_eq!(manifest.fallback_pool_size(), 1);
+        assert_eq!(manifest.zip_backed_package_count(), 0);
+
+        let manifest = &test_suites[4].manifest;
+        assert_eq!(manifest.package_count(), 4);
+        assert_eq!(manifest.alias_count(), 0);
+        assert_eq!(manifest.fallback_pool_size(), 2);
+        assert_eq!(manifest.zip_backed_package_count(), 2);
+    }
+
+    #[test]
+    fn test_transitive_dependencies_of() {
+        // A five-level deep chain, whose last package loops back to the first one
+        let json = r#"{
+            "enableTopLevelFallback": false,
+            "ignorePatternData": null,
+            "dependencyTreeRoots": [],
+            "fallbackPool": [],
+            "fallbackExclusionList": [],
+            "packageRegistryData": [
+                [null, [[null, {"packageLocation": "./", "packageDependencies": [["a", "npm:1.0.0"]]}]]],
+                ["a", [["npm:1.0.0", {"packageLocation": "./a/", "packageDependencies": [["b", "npm:1.0.0"]]}]]],
+                ["b", [["npm:1.0.0", {"packageLocation": "./b/", "packageDependencies": [["c", "npm:1.0.0"], ["peer", null]]}]]],
+                ["c", [["npm:1.0.0", {"packageLocation": "./c/", "packageDependencies": [["d", "npm:1.0.0"]]}]]],
+                ["d", [["npm:1.0.0", {"packageLocation": "./d/", "packageDependencies": [["e", "npm:1.0.0"]]}]]],
+                ["e", [["npm:1.0.0", {"packageLocation": "./e/", "packageDependencies": [["a", "npm:1.0.0"], ["missing", "npm:1.0.0"]]}]]]
+            ]
+        }"#;
+
+        let mut manifest: Manifest = serde_json::from_str(json).unwrap();
+        init_pnp_manifest(&mut manifest, Path::new("/path/to/project/.pnp.cjs"));
+
+        let locator = |name: &str| PackageLocator {
+            name: name.to_string(),
+            reference: if name.is_empty() { String::new() } else { "npm:1.0.0".to_string() },
         };
 
-        let issuer = std::env::current_dir().unwrap().
-            join("data/.yarn/unplugged/@carbon-icons-react-virtual-379302d360/node_modules/@carbon/icons-react/es/");
+        let names = |dependencies: FxHashSet<PackageLocator>| {
+            let mut names: Vec<String> =
+                dependencies.into_iter().map(|locator| locator.name).collect();
+            names.sort();
+            names
+        };
 
-        let resolution =
-            resolve_to_unqualified_via_manifest(&manifest, "@carbon/icon-helpers", &issuer)
-                .unwrap();
+        let dependencies = manifest.transitive_dependencies_of(&locator(""), Some(5)).unwrap();
+        assert_eq!(names(dependencies), vec!["a", "b", "c", "d", "e"]);
 
-        match resolution {
-            Resolution::Resolved(resolved, _) => {
-                assert!(resolved.ends_with(".yarn/unplugged/@carbon-icon-helpers-npm-10.54.0-a58f8b7b6c/node_modules/@carbon/icon-helpers"))
-            }
-            _ => {
-                panic!("Unexpected resolve failed");
-            }
+        let dependencies = manifest.transitive_dependencies_of(&locator(""), Some(2)).unwrap();
+        assert_eq!(names(dependencies), vec!["a", "b"]);
+
+        let dependencies = manifest.transitive_dependencies_of(&locator("c"), Some(0)).unwrap();
+        assert!(dependencies.is_empty());
+
+        // Without a depth limit, the `missing` package is reached from `e`
+        assert!(matches!(
+            manifest.transitive_dependencies_of(&locator(""), None),
+            Err(Error::FailedManifestHydration(_))
+        ));
+
+        // The cycle goes back to `a`, but the root never lists itself
+        let dependencies = manifest.transitive_dependencies_of(&locator("a"), Some(4)).unwrap();
+        assert_eq!(names(dependencies), vec!["b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_peer_dependencies() {
+        let json = r#"{
+            "enableTopLevelFallback": false,
+            "ignorePatternData": null,
+            "dependencyTreeRoots": [],
+            "fallbackPool": [],
+            "fallbackExclusionList": [],
+            "packageRegistryData": [
+                [null, [[null, {"packageLocation": "./", "packageDependencies": [["react", "npm:18.0.0"]]}]]],
+                ["react", [["npm:18.0.0", {"packageLocation": "./react/", "packageDependencies": []}]]],
+                ["react-dom", [
+                    ["npm:18.0.0", {"packageLocation": "./react-dom/", "packageDependencies": [["react", null]]}],
+                    ["virtual:abc#npm:18.0.0", {"packageLocation": "./react-dom/", "packageDependencies": [["react", "npm:18.0.0"]], "packagePeers": ["react"]}],
+                    ["virtual:def#npm:18.0.0", {"packageLocation": "./react-dom/", "packageDependencies": [["react", null]], "packagePeers": ["react"]}]
+                ]]
+            ]
+        }"#;
+
+        let mut manifest: Manifest = serde_json::from_str(json).unwrap();
+        init_pnp_manifest(&mut manifest, Path::new("/path/to/project/.pnp.cjs"));
+
+        let locator = |reference: &str| PackageLocator {
+            name: "react-dom".to_string(),
+            reference: reference.to_string(),
+        };
+
+        assert_eq!(
+            manifest.packages_requiring_peer("react").collect::<Vec<_>>(),
+            vec![locator("virtual:abc#npm:18.0.0"), locator("virtual:def#npm:18.0.0")]
+        );
+        assert_eq!(manifest.packages_requiring_peer("react-dom").count(), 0);
+
+        assert_eq!(
+            manifest.unsatisfied_peers(),
+            vec![
+                (locator("npm:18.0.0"), "react".to_string()),
+                (locator("virtual:def#npm:18.0.0"), "react".to_string()),
+            ]
+        );
+
+        let json = serialize_pnp_json(&manifest).unwrap();
+        assert!(json.contains("\"packagePeers\":[\"react\"]"));
+    }
+
+    #[test]
+    fn test_package_information_dependencies() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[0].manifest;
+
+        let info = &manifest.package_registry_data["workspace-unfulfilled-peer-dependency"]["workspace:workspace-unfulfilled-peer-dependency"];
+
+        assert!(info.has_dependency("test"));
+        assert!(!info.has_dependency("alias"));
+        assert_eq!(info.dependency_names().collect::<Vec<_>>(), vec!["test"]);
+        assert_eq!(info.declared_dependency_count(), 1);
+
+        let info = &manifest.package_registry_data["test"]["npm:1.0.0"];
+
+        assert_eq!(info.dependency_names().count(), 0);
+        assert_eq!(info.declared_dependency_count(), 0);
+    }
+
+    #[test]
+    fn test_zip_backed_packages() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[4].manifest;
+
+        let locators: Vec<PackageLocator> =
+            manifest.iter_packages().map(|(locator, _)| locator).collect();
+
+        let zip_backed: Vec<(PackageLocator, &Path)> = manifest.zip_backed_packages().collect();
+        assert_eq!(zip_backed.len(), manifest.zip_backed_package_count());
+
+        for (locator, path) in zip_backed {
+            assert!(locators.contains(&locator));
+            assert!(path.to_string_lossy().contains(".zip/"));
         }
     }
 
     #[test]
-    fn test_parse_single_package_name() {
-        let parsed = parse_bare_identifier("pkg");
-        assert_eq!(parsed, Ok(("pkg".to_string(), None)));
+    fn test_ignored_packages() {
+        let mut test_suites = load_test_suites();
+        let manifest = &mut test_suites[3].manifest;
+
+        assert_eq!(manifest.ignored_packages().count(), 0);
+        assert!(manifest.is_ignored_path("/path/to/project/not-a-workspace/index.js"));
+        assert!(!manifest.is_ignored_path("/path/to/project/test/index.js"));
+
+        manifest.ignore_pattern_data = Some(crate::util::RegexDef("^test(/|$)".parse().unwrap()));
+
+        let ignored: Vec<PackageLocator> =
+            manifest.ignored_packages().map(|(locator, _)| locator).collect();
+
+        assert_eq!(
+            ignored,
+            vec![PackageLocator { name: "test".to_string(), reference: "npm:1.0.0".to_string() }]
+        );
+        assert!(manifest.is_ignored_path("/path/to/project/test/index.js"));
+        assert!(!manifest.is_ignored_path("/path/to/project/index.js"));
+
+        manifest.ignore_pattern_data = None;
+
+        assert_eq!(manifest.ignored_packages().count(), 0);
+        assert!(!manifest.is_ignored_path("/path/to/project/test/index.js"));
     }
 
     #[test]
-    fn test_parse_scoped_package_name() {
-        let parsed = parse_bare_identifier("@scope/pkg");
-        assert_eq!(parsed, Ok(("@scope/pkg".to_string(), None)));
+    fn test_package_locator_ord() {
+        let locator = |name: &str, reference: &str| PackageLocator {
+            name: name.to_string(),
+            reference: reference.to_string(),
+        };
+
+        let mut locators = vec![
+            locator("lodash", "npm:4.17.21"),
+            locator("@types/node", "npm:20.0.0"),
+            locator("lodash", "npm:4.17.20"),
+            locator("", ""),
+            locator("@babel/core", "npm:7.0.0"),
+            locator("root", "workspace:."),
+            locator("@types/node", "npm:18.0.0"),
+        ];
+
+        locators.sort();
+
+        assert_eq!(
+            locators,
+            vec![
+                locator("", ""),
+                locator("@babel/core", "npm:7.0.0"),
+                locator("@types/node", "npm:18.0.0"),
+                locator("@types/node", "npm:20.0.0"),
+                locator("lodash", "npm:4.17.20"),
+                locator("lodash", "npm:4.17.21"),
+                locator("root", "workspace:."),
+            ]
+        );
+
+        assert!(locator("a", "npm:2.0.0") < locator("b", "npm:1.0.0"));
     }
 
     #[test]
-    fn test_parse_package_name_with_long_subpath() {
-        let parsed = parse_bare_identifier("pkg/a/b/c/index.js");
-        assert_eq!(parsed, Ok(("pkg".to_string(), Some("a/b/c/index.js".to_string()))));
+    fn test_package_locator_hash() {
+        fn hash(locator: &PackageLocator) -> u64 {
+            let mut hasher = FxHasher::default();
+            locator.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let locator = |name: &str, reference: &str| PackageLocator {
+            name: name.to_string(),
+            reference: reference.to_string(),
+        };
+
+        assert_eq!(
+            hash(&locator("lodash", "npm:4.17.21")),
+            hash(&locator("lodash", "npm:4.17.21"))
+        );
+        assert_ne!(
+            hash(&locator("lodash", "npm:4.17.21")),
+            hash(&locator("lodash", "npm:4.17.20"))
+        );
+        assert_ne!(hash(&locator("ab", "c")), hash(&locator("a", "bc")));
+    }
+
+    #[rstest]
+    #[case("lodash@npm:4.17.21", "lodash", "npm:4.17.21")]
+    #[case("@babel/core@npm:7.0.0", "@babel/core", "npm:7.0.0")]
+    #[case("my-app@workspace:.", "my-app", "workspace:.")]
+    #[case("@app/server@workspace:packages/server", "@app/server", "workspace:packages/server")]
+    #[case(
+        "typescript@patch:typescript@npm%3A5.0.0#~builtin<compat/typescript>",
+        "typescript",
+        "patch:typescript@npm%3A5.0.0#~builtin<compat/typescript>"
+    )]
+    fn test_locator_from_pnp_string(
+        #[case] input: &str,
+        #[case] name: &str,
+        #[case] reference: &str,
+    ) {
+        let locator = PackageLocator::from_pnp_string(input).unwrap();
+
+        assert_eq!(locator.name, name);
+        assert_eq!(locator.reference, reference);
+    }
+
+    #[rstest]
+    #[case("lodash")]
+    #[case("@babel/core")]
+    #[case("@babel@npm:7.0.0")]
+    #[case("lodash@")]
+    #[case("@npm:1.0.0")]
+    fn test_locator_from_invalid_pnp_string(#[case] input: &str) {
+        let err = PackageLocator::from_pnp_string(input).unwrap_err();
+
+        assert_eq!(err.specifier, input);
     }
 
     #[test]
-    fn test_parse_scoped_package_with_long_subpath() {
-        let parsed = parse_bare_identifier("@scope/pkg/a/b/c/index.js");
-        assert_eq!(parsed, Ok(("@scope/pkg".to_string(), Some("a/b/c/index.js".to_string()))));
+    fn test_workspace_relative_path() {
+        let workspace_path = |reference: &str| {
+            PackageLocator { name: String::from("pkg"), reference: reference.to_string() }
+                .workspace_relative_path()
+                .map(Path::to_path_buf)
+        };
+
+        assert_eq!(workspace_path("workspace:."), Some(PathBuf::from(".")));
+        assert_eq!(workspace_path("workspace:./packages/a"), Some(PathBuf::from("packages/a")));
+        assert_eq!(workspace_path("workspace:../shared/b"), Some(PathBuf::from("../shared/b")));
+        assert_eq!(workspace_path("npm:1.0.0"), None);
+        assert_eq!(workspace_path("virtual:abc#workspace:./packages/a"), None);
+    }
+
+    #[test]
+    fn test_iter_packages() {
+        let manifest =
+            load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+
+        let locators: Vec<PackageLocator> =
+            manifest.iter_packages().map(|(locator, _)| locator).collect();
+
+        assert_eq!(locators.len(), manifest.package_count() - 1);
+        assert!(!locators.contains(&PackageLocator::default()));
+
+        let locators: Vec<PackageLocator> =
+            manifest.iter_packages_with_sentinel().map(|(locator, _)| locator).collect();
+
+        assert_eq!(locators.len(), manifest.package_count());
+        assert_eq!(locators[0], PackageLocator::default());
+    }
+
+    #[test]
+    fn test_manifest_into_iterator() {
+        let manifest =
+            load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+
+        let mut locators = vec![];
+        for (locator, info) in &manifest {
+            assert_ne!(locator, PackageLocator::default());
+            assert_eq!(manifest.get_package(&locator).unwrap(), info);
+            locators.push(locator);
+        }
+
+        assert_eq!(locators.len(), manifest.package_count() - 1);
+        assert_eq!(
+            locators,
+            manifest.iter_packages().map(|(locator, _)| locator).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_manifest_methods() {
+        let manifest =
+            load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+
+        let issuer =
+            manifest.manifest_dir.join(".yarn/unplugged/lib-virtual-35bde7b160/node_modules/lib");
+
+        let (locator, _) = manifest.find_locator(&issuer).unwrap();
+        assert_eq!(manifest.get_package(locator).unwrap().package_location, issuer.join(""));
+        assert!(!manifest.is_dependency_tree_root(locator));
+        assert!(!manifest.is_excluded_from_fallback(locator));
+
+        assert_eq!(manifest.root_location(), manifest.manifest_dir);
+        assert_eq!(
+            manifest.relative_to_root(&issuer),
+            Path::new(".yarn/unplugged/lib-virtual-35bde7b160/node_modules/lib")
+        );
+
+        match manifest.resolve_to_unqualified("inner-package", &issuer) {
+            Ok(Resolution::Resolved(path, subpath)) => {
+                assert!(path.ends_with("path/to/inner-package"));
+                assert_eq!(subpath, None);
+            }
+            _ => {
+                panic!("Unexpected resolve failed");
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_installed() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[0].manifest;
+
+        assert!(manifest.is_installed("test", "npm:2.0.0"));
+        assert!(!manifest.is_installed("test", "npm:4.0.0"));
+        assert!(!manifest.is_installed("lodash", "npm:4.17.21"));
+
+        assert_eq!(
+            manifest.installed_versions("test").collect::<Vec<_>>(),
+            vec!["npm:1.0.0", "npm:2.0.0", "npm:3.0.0"]
+        );
+        assert_eq!(manifest.installed_versions("lodash").count(), 0);
+
+        assert!(manifest.is_any_version_installed("test"));
+        assert!(!manifest.is_any_version_installed("lodash"));
+
+        let locator = |name: &str, reference: &str| PackageLocator {
+            name: name.to_string(),
+            reference: reference.to_string(),
+        };
+
+        assert_eq!(
+            manifest.package_location_for(&locator("test", "npm:2.0.0")),
+            Some(
+                manifest
+                    .get_package(&locator("test", "npm:2.0.0"))
+                    .unwrap()
+                    .package_location
+                    .as_path()
+            )
+        );
+        assert_eq!(manifest.package_location_for(&locator("test", "npm:4.0.0")), None);
+        assert_eq!(manifest.package_location_for(&locator("lodash", "npm:4.17.21")), None);
+    }
+
+    #[test]
+    fn test_fallback_for() {
+        let mut test_suites = load_test_suites();
+        let manifest = &mut test_suites[1].manifest;
+
+        assert_eq!(
+            manifest.fallback_for("test-2"),
+            Some(&PackageDependency::Reference("npm:1.0.0".to_string()))
+        );
+        assert_eq!(
+            manifest.fallback_for("alias"),
+            Some(&PackageDependency::Alias("test-1".to_string(), "npm:1.0.0".to_string()))
+        );
+        assert_eq!(manifest.fallback_for("test-3"), None);
+
+        manifest.fallback_pool.insert("peer".to_string(), None);
+        assert_eq!(manifest.fallback_for("peer"), None);
+
+        let excluded = PackageLocator {
+            name: "workspace-no-fallbacks".to_string(),
+            reference: "workspace:workspace-no-fallbacks".to_string(),
+        };
+        let included = PackageLocator {
+            name: "workspace-with-fallbacks".to_string(),
+            reference: "workspace:workspace-with-fallbacks".to_string(),
+        };
+
+        assert!(manifest.is_excluded_from_fallback(&excluded));
+        assert!(!manifest.is_excluded_from_fallback(&included));
+    }
+
+    #[test]
+    fn test_on_fallback() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[1].manifest;
+
+        let fallbacks = Arc::new(Mutex::new(vec![]));
+
+        let config = ResolutionConfig {
+            on_fallback: Some(Arc::new({
+                let fallbacks = fallbacks.clone();
+                move |name, locator| {
+                    fallbacks.lock().unwrap().push((name.to_string(), locator.clone()))
+                }
+            })),
+            ..Default::default()
+        };
+
+        let issuer = Path::new("/path/to/project/workspace-with-fallbacks/index.js");
+
+        for specifier in ["test-1", "test-2/index.js"] {
+            let resolution = resolve_to_unqualified_via_manifest_with_config(
+                manifest, specifier, issuer, &config,
+            );
+
+            assert!(matches!(resolution, Ok(Resolution::Resolved(_, _))));
+        }
+
+        // Declared dependencies don't go through the fallback pool
+        let resolution = resolve_to_unqualified_via_manifest_with_config(
+            manifest,
+            "test-1",
+            Path::new("/path/to/project/index.js"),
+            &config,
+        );
+
+        assert!(matches!(resolution, Ok(Resolution::Resolved(_, _))));
+
+        let fallbacks = fallbacks.lock().unwrap();
+        let names: Vec<&str> = fallbacks.iter().map(|(name, _)| name.as_str()).collect();
+
+        assert_eq!(names, ["test-1", "test-2"]);
+        assert!(fallbacks.iter().all(|(_, locator)| locator.name == "workspace-with-fallbacks"));
+    }
+
+    #[test]
+    fn test_init_pnp_manifest_with_progress() {
+        let expectations_path =
+            std::env::current_dir().unwrap().join("data/test-expectations.json");
+        let manifest_content = fs::read_to_string(&expectations_path).unwrap();
+
+        let mut test_suites: Vec<TestSuite> = serde_json::from_str(&manifest_content).unwrap();
+
+        let manifest = &mut test_suites[0].manifest;
+
+        let events = RefCell::new(Vec::new());
+        init_pnp_manifest_with_progress(
+            manifest,
+            Path::new("/path/to/project/.pnp.cjs"),
+            &|current, total| events.borrow_mut().push((current, total)),
+        );
+
+        let events = events.into_inner();
+        let total = manifest.package_count();
+
+        assert_eq!(events.len(), total);
+        assert!(events.iter().all(|&(_, event_total)| event_total == total));
+        assert!(events.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(events.last(), Some(&(total, total)));
+    }
+
+    #[test]
+    fn test_init_pnp_manifest_with_zip_cache() {
+        use crate::fs::{LruZipCache, ZipCache, open_zip_via_read_p};
+
+        let mut manifest = parse_manifest(serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+            ["app", [["workspace:.", {"packageLocation": "./", "packageDependencies": []}]]],
+            ["left-pad", [
+                ["npm:1.0.0", {"packageLocation": "./fixtures/left-pad-1.zip/node_modules/left-pad/", "packageDependencies": []}],
+                ["npm:2.0.0", {"packageLocation": "./fixtures/does-not-exist.zip/node_modules/left-pad/", "packageDependencies": []}],
+            ]],
+        ]));
+
+        let cache = LruZipCache::new(32, open_zip_via_read_p);
+        let errors = init_pnp_manifest_with_zip_cache(
+            &mut manifest,
+            &env::current_dir().unwrap().join(".pnp.cjs"),
+            &cache,
+        );
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_find_locator() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[0].manifest;
+
+        let (locator, root) = find_locator(
+            manifest,
+            Path::new("/path/to/project/workspace-alias-dependency/lib/index.js"),
+        )
+        .unwrap();
+
+        assert_eq!(locator.name, "workspace-alias-dependency");
+        assert_eq!(root, Path::new("/path/to/project/workspace-alias-dependency/"));
+
+        let (locator, root) =
+            find_locator(manifest, Path::new("/path/to/project/index.js")).unwrap();
+
+        assert_eq!(locator.name, "root");
+        assert_eq!(root, Path::new("/path/to/project/"));
+
+        // Directories are matched with or without a trailing slash
+        for path in [
+            "/path/to/project/workspace-alias-dependency",
+            "/path/to/project/workspace-alias-dependency/",
+            "/path/to/project/workspace-alias-dependency/lib",
+        ] {
+            let (locator, _) = find_locator(manifest, Path::new(path)).unwrap();
+            assert_eq!(locator.name, "workspace-alias-dependency", "{path}");
+        }
+
+        // Paths sharing a prefix with a package location don't belong to it
+        let (locator, _) =
+            find_locator(manifest, Path::new("/path/to/project/workspace-alias-dependency.js"))
+                .unwrap();
+
+        assert_eq!(locator.name, "root");
+    }
+
+    #[test]
+    fn test_get_package_at_path() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[4].manifest;
+
+        let (locator, info) =
+            get_package_at_path(manifest, Path::new("/path/to/project/src/index.js"))
+                .unwrap()
+                .unwrap();
+
+        assert_eq!(locator.name, "my-project");
+        assert_eq!(info.package_location, Path::new("/path/to/project/"));
+
+        let (zip_locator, zip_location) = manifest.zip_backed_packages().next().unwrap();
+
+        let (locator, info) =
+            get_package_at_path(manifest, &zip_location.join("index.js")).unwrap().unwrap();
+
+        assert_eq!(*locator, zip_locator);
+        assert_eq!(info.package_location, zip_location);
+
+        assert!(get_package_at_path(manifest, Path::new("/outside/of/the/project")).is_none());
+    }
+
+    #[test]
+    fn test_packages_at_path() {
+        let manifest = manifest_with_registry(serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+            ["app", [["workspace:.", {"packageLocation": "./", "packageDependencies": [["a", "npm:1.0.0"]]}]]],
+            ["a", [["npm:1.0.0", {
+                "packageLocation": "./node_modules/a/",
+                "packageDependencies": [["b", "npm:1.0.0"]],
+            }]]],
+            ["b", [["npm:1.0.0", {
+                "packageLocation": "./node_modules/a/node_modules/b/",
+                "packageDependencies": [],
+            }]]],
+        ]));
+
+        let names = |path: &str| -> Vec<String> {
+            manifest
+                .packages_at_path(Path::new(path))
+                .into_iter()
+                .map(|(locator, info)| {
+                    assert!(Path::new(path).starts_with(&info.package_location));
+                    locator.name.clone()
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            names("/path/to/project/node_modules/a/node_modules/b/index.js"),
+            ["b", "a", "app"]
+        );
+        assert_eq!(names("/path/to/project/node_modules/a/lib/index.js"), ["a", "app"]);
+        assert_eq!(names("/path/to/project/index.js"), ["app"]);
+        assert!(names("/outside/of/the/project").is_empty());
+    }
+
+    #[test]
+    fn test_package_information_builder() {
+        let mut manifest = parse_manifest(serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+        ]));
+
+        let app = PackageInformationBuilder::new(PathBuf::from("./"))
+            .add_dependency("left-pad", Some(PackageDependency::Reference("npm:1.3.0".to_string())))
+            .add_dependency("react", None)
+            .build();
+
+        let left_pad =
+            PackageInformationBuilder::new(PathBuf::from("./node_modules/left-pad/")).build();
+
+        let left_pad_copy = PackageInformationBuilder::new(PathBuf::from("./node_modules/copy/"))
+            .discard_from_lookup(true)
+            .build();
+
+        assert!(app.has_dependency("react"));
+        assert_eq!(app.declared_dependency_count(), 2);
+
+        let mut insert = |name: &str, reference: &str, info: PackageInformation| {
+            manifest
+                .package_registry_data
+                .entry(name.to_string())
+                .or_default()
+                .insert(reference.to_string(), info);
+        };
+
+        insert("app", "workspace:.", app);
+        insert("left-pad", "npm:1.3.0", left_pad);
+        insert("left-pad", "npm:1.3.0-copy", left_pad_copy);
+
+        init_pnp_manifest(&mut manifest, Path::new("/path/to/project/.pnp.cjs"));
+
+        let issuer = Path::new("/path/to/project/index.js");
+
+        match resolve_to_unqualified_via_manifest(&manifest, "left-pad", issuer) {
+            Ok(Resolution::Resolved(path, None)) => {
+                assert_eq!(path, Path::new("/path/to/project/node_modules/left-pad/"));
+            }
+            resolution => panic!("Expected a resolution, got {resolution:?}"),
+        }
+
+        assert!(matches!(
+            resolve_to_unqualified_via_manifest(&manifest, "react", issuer),
+            Err(Error::MissingPeerDependency(_))
+        ));
+
+        assert!(
+            find_locator(&manifest, Path::new("/path/to/project/node_modules/copy/index.js"))
+                .is_some_and(|(locator, _)| locator.name == "app")
+        );
+    }
+
+    #[test]
+    fn test_manifest_index() {
+        let manifest =
+            load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+
+        for (locator, info) in &manifest {
+            assert_eq!(&manifest[&locator], info);
+            assert_eq!(&manifest[(locator.name.as_str(), locator.reference.as_str())], info);
+        }
+
+        assert_eq!(manifest[("", "")].package_location, manifest.manifest_dir.join(""));
+    }
+
+    #[test]
+    #[should_panic(expected = "Should have an entry in the package registry for lodash@npm:0.0.0")]
+    fn test_manifest_index_missing() {
+        let manifest =
+            load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+
+        let _ = &manifest
+            [&PackageLocator { name: "lodash".to_string(), reference: "npm:0.0.0".to_string() }];
+    }
+
+    #[test]
+    fn test_package_location_map() {
+        let manifest = manifest_with_registry(serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+            ["app", [["workspace:.", {"packageLocation": "./", "packageDependencies": [["a", "npm:1.0.0"]]}]]],
+            ["a", [
+                ["npm:1.0.0", {"packageLocation": "./node_modules/a/", "packageDependencies": []}],
+                ["npm:2.0.0", {
+                    "packageLocation": "./node_modules/a-2/",
+                    "packageDependencies": [],
+                    "discardFromLookup": true,
+                }],
+            ]],
+        ]));
+
+        let map = manifest.package_location_map();
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map[Path::new("/path/to/project/")].name, "app");
+        assert_eq!(map[Path::new("/path/to/project/node_modules/a")].reference, "npm:1.0.0");
+
+        assert!(!map.contains_key(Path::new("/path/to/project/node_modules/a-2/")));
+        assert!(!map.contains_key(Path::new("/path/to/project/node_modules/a/index.js")));
+    }
+
+    #[test]
+    fn test_init_pnp_manifest_checked() {
+        let package_registry_data = serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+            ["app", [["workspace:.", {"packageLocation": "./", "packageDependencies": [["a", "npm:1.0.0"]]}]]],
+            ["a", [["npm:1.0.0", {"packageLocation": "./node_modules/a/", "packageDependencies": []}]]],
+        ]);
+
+        let mut manifest = parse_manifest(package_registry_data.clone());
+        let warnings =
+            init_pnp_manifest_checked(&mut manifest, Path::new("/path/to/project/.pnp.cjs"));
+        assert_eq!(warnings, vec![]);
+
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+            let manifest_path =
+                Path::new(OsStr::from_bytes(b"/path/to/pr\xffject/.pnp.cjs")).to_path_buf();
+
+            let mut manifest = parse_manifest(package_registry_data);
+            let mut warnings = init_pnp_manifest_checked(&mut manifest, &manifest_path);
+            warnings.sort_by(|a, b| a.locator().cmp(b.locator()));
+
+            assert_eq!(
+                warnings,
+                vec![
+                    ManifestWarning::NonUtf8PackageLocation {
+                        locator: PackageLocator { name: "".to_string(), reference: "".to_string() },
+                        original_path: Path::new(OsStr::from_bytes(b"/path/to/pr\xffject/./"))
+                            .to_path_buf(),
+                    },
+                    ManifestWarning::NonUtf8PackageLocation {
+                        locator: PackageLocator {
+                            name: "a".to_string(),
+                            reference: "npm:1.0.0".to_string()
+                        },
+                        original_path: Path::new(OsStr::from_bytes(
+                            b"/path/to/pr\xffject/./node_modules/a/"
+                        ))
+                        .to_path_buf(),
+                    },
+                    ManifestWarning::NonUtf8PackageLocation {
+                        locator: PackageLocator {
+                            name: "app".to_string(),
+                            reference: "workspace:.".to_string()
+                        },
+                        original_path: Path::new(OsStr::from_bytes(b"/path/to/pr\xffject/./"))
+                            .to_path_buf(),
+                    },
+                ]
+            );
+
+            assert_eq!(
+                manifest.package_location_for(&PackageLocator {
+                    name: "a".to_string(),
+                    reference: "npm:1.0.0".to_string()
+                }),
+                Some(Path::new("/path/to/pr\u{FFFD}ject/node_modules/a/"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_package_hash() {
+        let load = |dependencies: serde_json::Value, manifest_path: &str| {
+            let mut manifest = parse_manifest(serde_json::json!([
+                [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+                ["app", [["workspace:.", {"packageLocation": "./", "packageDependencies": dependencies}]]],
+                ["a", [["npm:1.0.0", {"packageLocation": "./node_modules/a/", "packageDependencies": []}]]],
+                ["b", [["npm:1.0.0", {"packageLocation": "./node_modules/b/", "packageDependencies": []}]]],
+            ]));
+
+            init_pnp_manifest(&mut manifest, Path::new(manifest_path));
+            manifest
+        };
+
+        let app = PackageLocator { name: "app".to_string(), reference: "workspace:.".to_string() };
+        let a = PackageLocator { name: "a".to_string(), reference: "npm:1.0.0".to_string() };
+        let b = PackageLocator { name: "b".to_string(), reference: "npm:1.0.0".to_string() };
+
+        let manifest = load(
+            serde_json::json!([["a", "npm:1.0.0"], ["b", "npm:1.0.0"]]),
+            "/path/to/project/.pnp.cjs",
+        );
+
+        let hash = manifest.generate_package_hash(&app).unwrap();
+        assert_eq!(hash.len(), 16);
+        assert_eq!(manifest.generate_package_hash(&app).unwrap(), hash);
+        assert_ne!(manifest.generate_package_hash(&a).unwrap(), hash);
+        assert_ne!(
+            manifest.generate_package_hash(&a).unwrap(),
+            manifest.generate_package_hash(&b).unwrap()
+        );
+
+        let reordered = load(
+            serde_json::json!([["b", "npm:1.0.0"], ["a", "npm:1.0.0"]]),
+            "/elsewhere/project/.pnp.cjs",
+        );
+        assert_eq!(reordered.generate_package_hash(&app).unwrap(), hash);
+
+        let changed =
+            load(serde_json::json!([["a", "npm:1.0.0"], ["b", null]]), "/path/to/project/.pnp.cjs");
+        assert_ne!(changed.generate_package_hash(&app).unwrap(), hash);
+
+        let missing = PackageLocator { name: "a".to_string(), reference: "npm:0.0.0".to_string() };
+        assert!(matches!(
+            manifest.generate_package_hash(&missing),
+            Err(Error::FailedManifestHydration(_))
+        ));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tracing_test::traced_test]
+    #[test]
+    fn test_resolution_tracing() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[1].manifest;
+
+        resolve_to_unqualified_via_manifest(
+            manifest,
+            "test-2",
+            Path::new("/path/to/project/workspace-with-fallbacks/index.js"),
+        )
+        .unwrap();
+
+        assert!(logs_contain("found locator"));
+        assert!(logs_contain("reference=workspace:workspace-with-fallbacks"));
+        assert!(logs_contain("looking up dependency dependency=\"test-2\""));
+        assert!(logs_contain("using fallback pool"));
+        assert!(!logs_contain("resolution skipped"));
+
+        resolve_to_unqualified_via_manifest(manifest, "test-2", Path::new("/outside/index.js"))
+            .unwrap();
+
+        assert!(logs_contain("resolution skipped parent=/outside/index.js"));
+    }
+
+    #[test]
+    fn test_resolve_self_reference() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[0].manifest;
+
+        match resolve_to_unqualified_via_manifest(
+            manifest,
+            "root/lib/index.js",
+            Path::new("/path/to/project/src/index.js"),
+        ) {
+            Ok(Resolution::Resolved(path, subpath)) => {
+                assert_eq!(path, Path::new("/path/to/project/"));
+                assert_eq!(subpath.as_deref(), Some("lib/index.js"));
+            }
+            _ => panic!("Expected the workspace to resolve to itself"),
+        }
+
+        // Only workspaces may implicitly import themselves
+        let result = resolve_to_unqualified_via_manifest(
+            manifest,
+            "workspace-alias-dependency",
+            Path::new("/path/to/project/workspace-alias-dependency/index.js"),
+        );
+
+        assert!(matches!(result, Err(Error::UndeclaredDependency(_))));
+    }
+
+    #[test]
+    fn test_find_locator_skipping_virtual_packages() {
+        let manifest =
+            load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+
+        let issuer = manifest
+            .manifest_dir
+            .join(".yarn/unplugged/lib-virtual-35bde7b160/node_modules/lib/index.js");
+
+        let (locator, _) = find_locator(&manifest, &issuer).unwrap();
+        assert!(locator.is_virtual());
+        assert!(manifest.is_virtual_package(locator));
+
+        let options = FindLocatorOptions { include_virtual: false };
+        let (locator, root) = find_locator_with_options(&manifest, &issuer, &options).unwrap();
+        assert!(!locator.is_virtual());
+        assert_eq!(locator, &PackageLocator::default());
+        assert_eq!(root, manifest.manifest_dir.join("").as_path());
+    }
+
+    #[test]
+    fn test_manifest_diff() {
+        let old = load_pnp_manifest(Path::new("data/pnp-yarn-v4-registry-data-order.cjs")).unwrap();
+        let mut new = old.clone();
+
+        assert_eq!(Manifest::diff(&old, &new), ManifestDiff::default());
+
+        let (moved, _) = old.iter_packages().next().unwrap();
+        let moved_location = new.manifest_dir.join("moved/");
+
+        new.package_registry_data[&moved.name][&moved.reference].package_location =
+            moved_location.clone();
+
+        let added = PackageInformation {
+            package_location: new.manifest_dir.join("added/"),
+            discard_from_lookup: false,
+            package_dependencies: Default::default(),
+            package_peers: Default::default(),
+        };
+
+        new.package_registry_data
+            .entry("added".to_string())
+            .or_default()
+            .insert("npm:1.0.0".to_string(), added.clone());
+
+        let diff = Manifest::diff(&old, &new);
+
+        let added_locator =
+            PackageLocator { name: "added".to_string(), reference: "npm:1.0.0".to_string() };
+
+        assert_eq!(diff.added, vec![(added_locator, &added)]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(
+            diff.location_changed,
+            vec![(
+                moved.clone(),
+                old.package_location_for(&moved).unwrap(),
+                moved_location.as_path()
+            )]
+        );
+
+        let diff = Manifest::diff(&new, &old);
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.location_changed.len(), 1);
+    }
+
+    #[test]
+    fn test_dependency_path_to() {
+        let manifest = manifest_with_registry(serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+            ["app", [["workspace:.", {"packageLocation": "./", "packageDependencies": [["a", "npm:1.0.0"]]}]]],
+            ["a", [["npm:1.0.0", {"packageLocation": "./a/", "packageDependencies": [["b", "npm:1.0.0"]]}]]],
+            ["b", [["npm:1.0.0", {
+                "packageLocation": "./b/",
+                "packageDependencies": [["c", "npm:1.0.0"], ["react", null]],
+                "packagePeers": ["react"],
+            }]]],
+            ["c", [["npm:1.0.0", {
+                "packageLocation": "./c/",
+                "packageDependencies": [["react", null]],
+                "packagePeers": ["react"],
+            }]]],
+            ["d", [["npm:1.0.0", {"packageLocation": "./d/", "packageDependencies": []}]]],
+        ]));
+
+        let locator = |name: &str, reference: &str| PackageLocator {
+            name: name.to_string(),
+            reference: reference.to_string(),
+        };
+
+        assert_eq!(
+            manifest.dependency_path_to(&locator("c", "npm:1.0.0")),
+            Some(vec![
+                locator("app", "workspace:."),
+                locator("a", "npm:1.0.0"),
+                locator("b", "npm:1.0.0"),
+                locator("c", "npm:1.0.0"),
+            ])
+        );
+        assert_eq!(
+            manifest.dependency_path_to(&locator("app", "workspace:.")),
+            Some(vec![locator("app", "workspace:.")])
+        );
+        assert_eq!(manifest.dependency_path_to(&locator("d", "npm:1.0.0")), None);
+
+        // `b` forwards the peer dependency, so `a` is the one that breaks it
+        assert_eq!(
+            manifest.find_broken_peer_dependencies("react", &locator("c", "npm:1.0.0")),
+            vec![locator("a", "npm:1.0.0")]
+        );
+        assert_eq!(
+            manifest.find_broken_peer_dependencies("react", &locator("d", "npm:1.0.0")),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_missing_peer_dependency_broken_ancestors() {
+        let manifest = manifest_with_registry(serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+            ["app", [["workspace:.", {
+                "packageLocation": "./",
+                "packageDependencies": [["a", "npm:1.0.0"], ["e", "npm:1.0.0"]],
+            }]]],
+            ["a", [["npm:1.0.0", {"packageLocation": "./a/", "packageDependencies": [["c", "npm:1.0.0"]]}]]],
+            ["c", [["npm:1.0.0", {
+                "packageLocation": "./c/",
+                "packageDependencies": [["react", null]],
+                "packagePeers": ["react"],
+            }]]],
+            ["e", [["npm:1.0.0", {
+                "packageLocation": "./e/",
+                "packageDependencies": [["react", null]],
+                "packagePeers": ["react"],
+            }]]],
+        ]));
+
+        let locator = |name: &str, reference: &str| PackageLocator {
+            name: name.to_string(),
+            reference: reference.to_string(),
+        };
+
+        let missing_peer = |parent: &str| match resolve_to_unqualified_via_manifest(
+            &manifest,
+            "react",
+            Path::new(parent),
+        ) {
+            Err(Error::MissingPeerDependency(err)) => err,
+            result => panic!("Expected a missing peer dependency, got {result:?}"),
+        };
+
+        let err = missing_peer("/path/to/project/c/index.js");
+        assert_eq!(err.broken_ancestors, vec![locator("a", "npm:1.0.0")]);
+        assert!(err.message.contains("but it isn't provided by its ancestors"), "{}", err.message);
+
+        let err = missing_peer("/path/to/project/e/index.js");
+        assert_eq!(err.broken_ancestors, vec![locator("app", "workspace:.")]);
+        assert!(
+            err.message.contains("but it isn't provided by your application"),
+            "{}",
+            err.message
+        );
+    }
+
+    #[test]
+    fn test_package_graph() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[0].manifest;
+
+        let locator = |name: &str, reference: &str| PackageLocator {
+            name: name.to_string(),
+            reference: reference.to_string(),
+        };
+
+        let graph = manifest.package_graph();
+
+        assert_eq!(graph.len(), manifest.package_count());
+        assert_eq!(
+            graph[&locator("workspace-alias-dependency", "workspace:workspace-alias-dependency")],
+            vec![locator("test", "npm:1.0.0")]
+        );
+        assert_eq!(
+            graph[&locator("workspace-self-dependency", "workspace:workspace-self-dependency")],
+            vec![locator("workspace-self-dependency", "workspace:workspace-self-dependency")]
+        );
+        assert!(
+            graph[&locator(
+                "workspace-unfulfilled-peer-dependency",
+                "workspace:workspace-unfulfilled-peer-dependency"
+            )]
+                .is_empty()
+        );
+        assert!(graph[&locator("test", "npm:1.0.0")].is_empty());
+    }
+
+    #[test]
+    fn test_manifest_display() {
+        let test_suites = load_test_suites();
+        let manifest = &test_suites[4].manifest;
+
+        assert_eq!(
+            manifest.to_string(),
+            "PnP Manifest @ /path/to/project/.pnp.cjs\n  4 packages (2 zip-backed)\n  fallback pool: 2 entries\n  ignore pattern: none"
+        );
+    }
+
+    #[test]
+    fn test_package_resolved_location() {
+        let test_suites = load_test_suites();
+
+        let native_pkg = &test_suites[3].manifest.package_registry_data["test"]["npm:1.0.0"];
+        assert!(!native_pkg.is_zip_backed());
+        assert!(matches!(native_pkg.resolved_location().unwrap(), VPath::Native(_)));
+
+        let zip_pkg = &test_suites[4].manifest.package_registry_data["pad-left"]["npm:2.1.0"];
+        assert!(zip_pkg.is_zip_backed());
+
+        match zip_pkg.resolved_location().unwrap() {
+            VPath::Zip(info) => {
+                assert_eq!(info.zip_path, "node_modules/pad-left/");
+            }
+            _ => {
+                panic!("Expected the package to be stored in a zip archive");
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_case_one_pkg_cached_and_unplugged() {
+        let manifest = {
+            let manifest_json_path =
+                std::env::current_dir().unwrap().join("./data/edge_case_manifest_state.json");
+            let manifest_content = fs::read_to_string(&manifest_json_path).unwrap();
+            let mut manifest = serde_json::from_str::<Manifest>(&manifest_content).unwrap();
+            init_pnp_manifest(&mut manifest, &manifest_json_path);
+            manifest
+        };
+
+        let issuer = std::env::current_dir().unwrap().
+            join("data/.yarn/unplugged/@carbon-icons-react-virtual-379302d360/node_modules/@carbon/icons-react/es/");
+
+        let resolution =
+            resolve_to_unqualified_via_manifest(&manifest, "@carbon/icon-helpers", &issuer)
+                .unwrap();
+
+        match resolution {
+            Resolution::Resolved(resolved, _) => {
+                assert!(resolved.ends_with(".yarn/unplugged/@carbon-icon-helpers-npm-10.54.0-a58f8b7b6c/node_modules/@carbon/icon-helpers"))
+            }
+            _ => {
+                panic!("Unexpected resolve failed");
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_closest_pnp_manifest_path_named() {
+        let issuer = Path::new("data/path/to/file");
+
+        assert_eq!(
+            find_closest_pnp_manifest_path_named(issuer, &["missing.cjs", "pnp-yarn-v4.cjs"]),
+            Some(PathBuf::from("data/pnp-yarn-v4.cjs"))
+        );
+        assert_eq!(find_closest_pnp_manifest_path_named(issuer, &["missing.cjs"]), None);
+
+        let host = ResolutionHost::with_manifest_filenames(vec!["pnp-yarn-v3.cjs".to_string()]);
+        let manifest = (host.find_pnp_manifest)(issuer).unwrap().unwrap();
+
+        assert_eq!(manifest.manifest_path, PathBuf::from("data/pnp-yarn-v3.cjs"));
+    }
+
+    #[cfg(feature = "yarnrc")]
+    #[test]
+    fn test_find_closest_pnp_manifest_path_respecting_yarnrc() {
+        use crate::find_closest_pnp_manifest_path_respecting_yarnrc;
+
+        let manifest_path = find_closest_pnp_manifest_path_respecting_yarnrc(Path::new(
+            "fixtures/yarnrc/packages/app/index.js",
+        ));
+        assert_eq!(manifest_path, Some(PathBuf::from("fixtures/yarnrc/config/custom-pnp.cjs")));
+
+        load_pnp_manifest(&manifest_path.unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_find_all_pnp_manifests_in_tree() {
+        let manifests = find_all_pnp_manifests_in_tree(Path::new("fixtures/nested-manifests"))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let manifest_paths: Vec<&Path> =
+            manifests.iter().map(|manifest| manifest.manifest_path.as_path()).collect();
+
+        assert_eq!(
+            manifest_paths,
+            vec![
+                Path::new("fixtures/nested-manifests/.pnp.cjs"),
+                Path::new("fixtures/nested-manifests/packages/app/.pnp.cjs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fallback_resolution_host() {
+        let manifest = load_pnp_manifest(Path::new("data/pnp-yarn-v3.cjs")).unwrap();
+
+        let empty_host = ResolutionHost { find_pnp_manifest: Arc::new(|_| Ok(None)) };
+
+        let manifest_host =
+            ResolutionHost { find_pnp_manifest: Arc::new(move |_| Ok(Some(manifest.clone()))) };
+
+        let host = FallbackResolutionHost::new(vec![empty_host, manifest_host]);
+
+        let found = host.find_pnp_manifest(Path::new("/path/to/file")).unwrap().unwrap();
+        assert_eq!(found.manifest_path, PathBuf::from("data/pnp-yarn-v3.cjs"));
+
+        let config = ResolutionConfig { host: host.into(), ..Default::default() };
+
+        let found = (config.host.find_pnp_manifest)(Path::new("/path/to/file")).unwrap();
+        assert!(found.is_some());
+
+        let host = FallbackResolutionHost::new(vec![]);
+        assert!(host.find_pnp_manifest(Path::new("/path/to/file")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_error_with_context() {
+        let err = parse_bare_identifier("@scope").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid specifier");
+
+        let err = err.with_context("@scope", Path::new("/path/to/file"));
+        assert_eq!(
+            err.to_string(),
+            "While resolving `@scope` from `/path/to/file`: Invalid specifier"
+        );
+
+        let err = err.with_context("pkg", Path::new("/path/to/other"));
+        assert_eq!(
+            err.to_string(),
+            "While resolving `pkg` from `/path/to/other`: While resolving `@scope` from `/path/to/file`: Invalid specifier"
+        );
+
+        match err {
+            Error::Context(ctx) => {
+                assert_eq!(ctx.specifier, "pkg");
+                assert!(matches!(ctx.error, Error::Context(_)));
+
+                let source = std::error::Error::source(ctx.as_ref())
+                    .expect("Assertion failed: Expected the wrapped error to be exposed");
+                assert_eq!(source.to_string(), ctx.error.to_string());
+            }
+            _ => {
+                panic!("Expected a contextual error");
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_accessors() {
+        let err = parse_bare_identifier("@scope").unwrap_err();
+
+        assert!(matches!(err, Error::BadSpecifier(_)));
+        assert_eq!(err.specifier(), None);
+        assert_eq!(err.issuer_path(), None);
+        assert_eq!(err.issuer_locator(), None);
+
+        let err = err.with_context("@scope", Path::new("/path/to/file"));
+        assert_eq!(err.specifier(), Some("@scope"));
+        assert_eq!(err.issuer_path(), Some(Path::new("/path/to/file")));
+
+        let manifest = load_pnp_manifest(Path::new("data/pnp-yarn-v4.cjs")).unwrap();
+        let issuer = manifest.manifest_dir.join("index.js");
+
+        let err = resolve_to_unqualified_via_manifest(&manifest, "not-a-dependency/lib", &issuer)
+            .unwrap_err()
+            .with_context("not-a-dependency/lib", &issuer);
+
+        assert!(
+            matches!(&err, Error::Context(context) if matches!(context.error, Error::UndeclaredDependency(_)))
+        );
+        assert_eq!(err.specifier(), Some("not-a-dependency/lib"));
+        assert_eq!(err.issuer_path(), Some(issuer.as_path()));
+        assert!(
+            err.issuer_locator().is_some_and(|locator| manifest.is_dependency_tree_root(locator))
+        );
+
+        let err = load_pnp_manifest(Path::new("data/missing.cjs")).unwrap_err();
+        assert_eq!(err.specifier(), None);
+        assert_eq!(err.issuer_path(), None);
+        assert_eq!(err.issuer_locator(), None);
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let parent = Path::new("/");
+
+        let resolution = resolve_to_unqualified("lodash", parent, &ResolutionConfig::default());
+        assert!(matches!(resolution, Ok(Resolution::Skipped)));
+
+        let config = ResolutionConfig { strict: true, ..Default::default() };
+
+        match resolve_to_unqualified("lodash", parent, &config) {
+            Err(Error::FailedManifestHydration(failure)) => {
+                assert!(failure.message.starts_with("No PnP manifest found"));
+                assert_eq!(failure.manifest_path, parent);
+            }
+            _ => {
+                panic!("Expected the resolution to fail in strict mode");
+            }
+        }
+
+        let mut manifest = manifest_with_registry(serde_json::json!([
+            [null, [[null, {"packageLocation": "./", "packageDependencies": [["app", "workspace:."]]}]]],
+            ["app", [["workspace:.", {"packageLocation": "./", "packageDependencies": []}]]],
+        ]));
+        manifest.ignore_pattern_data =
+            Some(crate::util::RegexDef("^ignored(/|$)".parse().unwrap()));
+
+        let host =
+            ResolutionHost { find_pnp_manifest: Arc::new(move |_| Ok(Some(manifest.clone()))) };
+        let config = ResolutionConfig { host, strict: true, ..Default::default() };
+
+        for (parent, expected_message) in [
+            ("/path/to/elsewhere/index.js", "The PnP manifest doesn't list any package containing"),
+            ("/path/to/project/ignored/index.js", "The PnP manifest ignores"),
+        ] {
+            match resolve_to_unqualified("lodash", Path::new(parent), &config) {
+                Err(Error::FailedManifestHydration(failure)) => {
+                    assert!(failure.message.starts_with(expected_message), "{}", failure.message);
+                    assert_eq!(failure.manifest_path, Path::new("/path/to/project/.pnp.cjs"));
+                }
+                _ => {
+                    panic!("Expected the resolution to fail in strict mode");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_single_package_name() {
+        let parsed = parse_bare_identifier("pkg");
+        assert_eq!(parsed, Ok(BareIdentifier { name: "pkg".to_string(), subpath: None }));
+    }
+
+    #[test]
+    fn test_parse_scoped_package_name() {
+        let parsed = parse_bare_identifier("@scope/pkg");
+        assert_eq!(parsed, Ok(BareIdentifier { name: "@scope/pkg".to_string(), subpath: None }));
+    }
+
+    #[test]
+    fn test_parse_package_name_with_long_subpath() {
+        let parsed = parse_bare_identifier("pkg/a/b/c/index.js");
+        assert_eq!(
+            parsed,
+            Ok(BareIdentifier {
+                name: "pkg".to_string(),
+                subpath: Some("a/b/c/index.js".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_scoped_package_with_long_subpath() {
+        let parsed = parse_bare_identifier("@scope/pkg/a/b/c/index.js");
+        assert_eq!(
+            parsed,
+            Ok(BareIdentifier {
+                name: "@scope/pkg".to_string(),
+                subpath: Some("a/b/c/index.js".to_string())
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("pkg")]
+    #[case("@scope/pkg")]
+    #[case("pkg/a/b/c/index.js")]
+    #[case("@scope/pkg/a/b/c/index.js")]
+    fn test_bare_identifier_display(#[case] specifier: &str) {
+        assert_eq!(parse_bare_identifier(specifier).unwrap().to_string(), specifier);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_parse_bare_identifier_tuple() {
+        let parsed = crate::parse_bare_identifier_tuple("@scope/pkg/index.js");
+        assert_eq!(parsed, Ok(("@scope/pkg".to_string(), Some("index.js".to_string()))));
     }
 
     #[test]
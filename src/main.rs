@@ -1,20 +1,122 @@
-use pnp::{Resolution, ResolutionConfig};
+use pnp::{Error, Manifest, Resolution, ResolutionConfig};
 use std::path::PathBuf;
 
+fn error_kind(err: &Error) -> &'static str {
+    match err {
+        Error::BadSpecifier(_) => "BadSpecifier",
+        Error::FailedManifestHydration(_) => "FailedManifestHydration",
+        Error::MissingPeerDependency(_) => "MissingPeerDependency",
+        Error::UndeclaredDependency(_) => "UndeclaredDependency",
+        Error::MissingDependency(_) => "MissingDependency",
+        Error::Context(context) => error_kind(&context.error),
+    }
+}
+
+fn list_packages(manifest: &Manifest, filter: Option<&str>, json: bool) {
+    let packages = manifest
+        .iter_packages()
+        .filter(|(locator, _)| filter.is_none_or(|filter| locator.name.contains(filter)));
+
+    if json {
+        let output: Vec<_> = packages
+            .map(|(locator, info)| {
+                serde_json::json!({
+                    "name": locator.name,
+                    "reference": locator.reference,
+                    "location": info.package_location,
+                })
+            })
+            .collect();
+
+        println!("{}", serde_json::Value::Array(output));
+        return;
+    }
+
+    for (locator, info) in packages {
+        println!("{}@{}\t{}", locator.name, locator.reference, info.package_location.display());
+    }
+}
+
 fn main() {
-    let mut args = std::env::args();
+    let mut json = false;
+    let mut manifest_path = None;
+    let mut filter = None;
+    let mut positionals = vec![];
 
     // Skip the program name
-    args.next();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--manifest" => {
+                manifest_path =
+                    Some(args.next().map(PathBuf::from).expect("A manifest path must be provided"));
+            }
+            "--filter" => {
+                filter = Some(args.next().expect("A filter must be provided"));
+            }
+            _ => positionals.push(arg),
+        }
+    }
 
-    let specifier = args.next().expect("A specifier must be provided");
+    let mut positionals = positionals.into_iter().peekable();
+
+    if positionals.next_if(|arg| arg == "list-packages").is_some() {
+        let manifest = match positionals.next().map(PathBuf::from).or(manifest_path) {
+            Some(manifest_path) => pnp::load_pnp_manifest(&manifest_path),
+            None => {
+                let cwd = std::env::current_dir().expect("Should have a working directory");
+                pnp::find_pnp_manifest(&cwd).map(|manifest| {
+                    manifest.expect("No PnP manifest found in the working directory")
+                })
+            }
+        };
 
-    let parent = args.next().map(PathBuf::from).expect("A parent url must be provided");
+        match manifest {
+            Ok(manifest) => list_packages(&manifest, filter.as_deref(), json),
+            Err(err) => println!("{err}"),
+        }
+
+        return;
+    }
 
-    println!("specifier = {specifier}");
-    println!("parent    = {parent:?}");
+    let specifier = positionals.next().expect("A specifier must be provided");
 
-    let resolution = pnp::resolve_to_unqualified(&specifier, &parent, &ResolutionConfig::default());
+    let parent = positionals.next().map(PathBuf::from).expect("A parent url must be provided");
+
+    if !json {
+        println!("specifier = {specifier}");
+        println!("parent    = {parent:?}");
+    }
+
+    let resolution = match manifest_path {
+        Some(manifest_path) => pnp::load_pnp_manifest(&manifest_path).and_then(|manifest| {
+            pnp::resolve_to_unqualified_via_manifest(&manifest, &specifier, &parent)
+        }),
+        None => pnp::resolve_to_unqualified(&specifier, &parent, &ResolutionConfig::default()),
+    };
+
+    if json {
+        let output = match resolution {
+            Ok(Resolution::Resolved(p, subpath)) => serde_json::json!({
+                "status": "resolved",
+                "path": p,
+                "subpath": subpath,
+            }),
+            Ok(Resolution::Skipped) => serde_json::json!({
+                "status": "skipped",
+            }),
+            Err(err) => serde_json::json!({
+                "status": "error",
+                "kind": error_kind(&err),
+                "message": err.to_string(),
+            }),
+        };
+
+        println!("{output}");
+        return;
+    }
 
     match resolution {
         Ok(res) => match res {
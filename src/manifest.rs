@@ -1,13 +1,28 @@
-use std::path::PathBuf;
+use std::{
+    fmt,
+    hash::Hasher,
+    ops::Index,
+    path::{Path, PathBuf},
+};
 
-use indexmap::IndexMap;
-use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet};
+use indexmap::{IndexMap, IndexSet};
+use rustc_hash::{FxBuildHasher, FxHashMap};
 
-use serde::{Deserialize, de::Deserializer};
+use serde::{
+    Deserialize, Serialize,
+    de::{Deserializer, Error as _, SeqAccess, Visitor},
+    ser::{SerializeStruct, Serializer},
+};
+use siphasher::sip::SipHasher13;
 
-use crate::util::{RegexDef, Trie};
+use crate::{
+    BadSpecifier, Error, FailedManifestHydration,
+    fs::VPath,
+    util::{self, RegexDef, Trie},
+};
 
 type FxIndexMap<K, V> = IndexMap<K, V, FxBuildHasher>;
+type FxIndexSet<T> = IndexSet<T, FxBuildHasher>;
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -21,6 +36,12 @@ pub struct Manifest {
     #[serde(skip_deserializing)]
     pub location_trie: Trie<PackageLocator>,
 
+    #[serde(skip_deserializing)]
+    pub location_map: FxHashMap<PathBuf, PackageLocator>,
+
+    #[serde(rename = "__metadata", default)]
+    pub metadata: Option<ManifestMetadata>,
+
     pub enable_top_level_fallback: bool,
     pub ignore_pattern_data: Option<RegexDef>,
 
@@ -28,21 +49,21 @@ pub struct Manifest {
     //   "name": "@app/monorepo",
     //   "workspace:."
     // }]
-    pub dependency_tree_roots: FxHashSet<PackageLocator>,
+    pub dependency_tree_roots: FxIndexSet<PackageLocator>,
 
     // fallbackPool: [[
     //   "@app/monorepo",
     //   "workspace:.",
     // ]]
     #[serde(deserialize_with = "deserialize_package_dependencies")]
-    pub fallback_pool: FxHashMap<String, Option<PackageDependency>>,
+    pub fallback_pool: FxIndexMap<String, Option<PackageDependency>>,
 
     // fallbackExclusionList: [[
     //   "@app/server",
     //  ["workspace:sources/server"],
     // ]]
     #[serde(deserialize_with = "deserialize_fallback_exclusion_list")]
-    pub fallback_exclusion_list: FxHashMap<String, FxHashSet<String>>,
+    pub fallback_exclusion_list: FxIndexMap<String, FxIndexSet<String>>,
 
     // packageRegistryData: [
     //   [null, [
@@ -55,41 +76,783 @@ pub struct Manifest {
     pub package_registry_data: FxIndexMap<String, FxIndexMap<String, PackageInformation>>,
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Deserialize)]
+/// The latest version of the manifest format this crate knows how to parse.
+pub const CURRENT_SUPPORTED_MANIFEST_VERSION: u32 = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ManifestMetadata {
+    pub version: u32,
+
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl Manifest {
+    /// Returns the root of the project, which is the directory containing
+    /// the manifest.
+    pub fn root_location(&self) -> &Path {
+        self.manifest_dir.as_path()
+    }
+
+    /// Returns the given path relative to the project root. The path must be
+    /// absolute.
+    pub fn relative_to_root<P: AsRef<Path>>(&self, p: P) -> PathBuf {
+        let p = p.as_ref();
+
+        pathdiff::diff_paths(p, self.root_location()).unwrap_or_else(|| {
+            panic!(
+                "Assertion failed: Provided path should be absolute but received {}",
+                p.display()
+            )
+        })
+    }
+
+    /// Iterates over all packages in the registry, except for the top-level
+    /// one (whose name and reference are both empty).
+    pub fn iter_packages(&self) -> PackageIter<'_> {
+        PackageIter { names: self.package_registry_data.iter(), current: None, sentinel: false }
+    }
+
+    /// Iterates over all packages in the registry, including the top-level
+    /// one.
+    pub fn iter_packages_with_sentinel(&self) -> PackageIter<'_> {
+        PackageIter { names: self.package_registry_data.iter(), current: None, sentinel: true }
+    }
+
+    /// Iterates over the packages whose location is stored within a zip
+    /// archive, along with their location.
+    pub fn zip_backed_packages(&self) -> impl Iterator<Item = (PackageLocator, &Path)> {
+        self.iter_packages_with_sentinel()
+            .filter(|(_, info)| info.is_zip_backed())
+            .map(|(locator, info)| (locator, info.package_location.as_path()))
+    }
+
+    /// Iterates over the packages whose location is matched by the ignore
+    /// pattern, and which are thus excluded from PnP resolution.
+    pub fn ignored_packages(&self) -> impl Iterator<Item = (PackageLocator, &PackageInformation)> {
+        self.iter_packages_with_sentinel()
+            .filter(|(_, info)| self.is_ignored_path(&info.package_location))
+    }
+
+    /// Checks whether the given path is matched by the ignore pattern, in
+    /// which case it's excluded from PnP resolution. The path must be
+    /// absolute.
+    pub fn is_ignored_path<P: AsRef<Path>>(&self, p: P) -> bool {
+        let Some(regex) = &self.ignore_pattern_data else {
+            return false;
+        };
+
+        let rel_path = self.relative_to_root(p);
+
+        regex.0.is_match(&util::normalize_path(rel_path.to_string_lossy())).unwrap()
+    }
+
+    /// Iterates over the packages that list the given package name as one of
+    /// their peer dependencies.
+    pub fn packages_requiring_peer<'a>(
+        &'a self,
+        peer_name: &'a str,
+    ) -> impl Iterator<Item = PackageLocator> + 'a {
+        self.iter_packages()
+            .filter(move |(_, info)| info.package_peers.contains(peer_name))
+            .map(|(locator, _)| locator)
+    }
+
+    /// Returns the peer dependencies that aren't provided to the packages
+    /// declaring them, along with the packages declaring them.
+    pub fn unsatisfied_peers(&self) -> Vec<(PackageLocator, String)> {
+        self.iter_packages_with_sentinel()
+            .flat_map(|(locator, info)| {
+                info.package_dependencies
+                    .iter()
+                    .filter(|(_, dependency)| dependency.is_none())
+                    .map(move |(name, _)| (locator.clone(), name.clone()))
+            })
+            .collect()
+    }
+
+    /// Returns the adjacency list of the dependency graph, mapping each
+    /// package to the packages its dependencies resolve to. Unfulfilled peer
+    /// dependencies aren't part of the graph.
+    pub fn package_graph(&self) -> FxHashMap<PackageLocator, Vec<PackageLocator>> {
+        self.iter_packages_with_sentinel()
+            .map(|(locator, info)| {
+                let dependencies = info
+                    .package_dependencies
+                    .iter()
+                    .filter_map(|(name, dependency)| {
+                        dependency.as_ref().map(|dependency| dependency.to_locator(name))
+                    })
+                    .collect();
+
+                (locator, dependencies)
+            })
+            .collect()
+    }
+
+    /// Compares the packages of two manifests. Packages present in both are
+    /// reported if their location changed. The top-level package is ignored.
+    pub fn diff<'a, 'b>(old: &'a Manifest, new: &'b Manifest) -> ManifestDiff<'a, 'b> {
+        let mut diff = ManifestDiff::default();
+
+        for (locator, old_info) in old.iter_packages() {
+            match new.get_package_information(&locator) {
+                None => diff.removed.push((locator, old_info)),
+                Some(new_info) => {
+                    if old_info.package_location != new_info.package_location {
+                        diff.location_changed.push((
+                            locator,
+                            &old_info.package_location,
+                            &new_info.package_location,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (locator, new_info) in new.iter_packages() {
+            if old.get_package_information(&locator).is_none() {
+                diff.added.push((locator, new_info));
+            }
+        }
+
+        diff
+    }
+
+    pub(crate) fn get_package_information(
+        &self,
+        locator: &PackageLocator,
+    ) -> Option<&PackageInformation> {
+        self.package_registry_data
+            .get(&locator.name)
+            .and_then(|references| references.get(&locator.reference))
+    }
+
+    /// Sorts the content of the manifest so that its serialization doesn't
+    /// depend on the order in which its entries were inserted. Note that the
+    /// location trie is left untouched, as the registry order is meaningful
+    /// when multiple packages share the same location.
+    pub fn sort(&mut self) {
+        self.dependency_tree_roots.sort();
+
+        self.fallback_pool.sort_keys();
+
+        self.fallback_exclusion_list.sort_keys();
+        for references in self.fallback_exclusion_list.values_mut() {
+            references.sort();
+        }
+
+        self.package_registry_data.sort_keys();
+        for references in self.package_registry_data.values_mut() {
+            references.sort_keys();
+            for info in references.values_mut() {
+                info.package_dependencies.sort_keys();
+                info.package_peers.sort();
+            }
+        }
+    }
+
+    /// Returns the number of packages in the registry, including the
+    /// top-level one.
+    pub fn package_count(&self) -> usize {
+        self.package_registry_data.values().map(|references| references.len()).sum()
+    }
+
+    /// Returns the number of dependencies that are aliases to packages
+    /// registered under a different name.
+    pub fn alias_count(&self) -> usize {
+        self.package_registry_data
+            .values()
+            .flat_map(|references| references.values())
+            .flat_map(|info| info.package_dependencies.values())
+            .filter(|dependency| matches!(dependency, Some(PackageDependency::Alias(_, _))))
+            .count()
+    }
+
+    pub fn fallback_pool_size(&self) -> usize {
+        self.fallback_pool.len()
+    }
+
+    pub fn is_installed(&self, name: &str, reference: &str) -> bool {
+        self.package_registry_data
+            .get(name)
+            .is_some_and(|references| references.contains_key(reference))
+    }
+
+    /// Iterates over the references under which the given package name is
+    /// installed, virtual ones included.
+    pub fn installed_versions<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a str> {
+        self.package_registry_data
+            .get(name)
+            .into_iter()
+            .flat_map(|references| references.keys().map(String::as_str))
+    }
+
+    /// Returns the location of the given package, or `None` if it isn't
+    /// part of the registry.
+    pub fn package_location_for<'a>(&'a self, locator: &PackageLocator) -> Option<&'a Path> {
+        self.get_package_information(locator).map(|info| info.package_location.as_path())
+    }
+
+    /// Returns the locators of the packages indexed by their exact location.
+    /// Unlike `find_locator`, a path within a package doesn't match it.
+    pub fn package_location_map(&self) -> &FxHashMap<PathBuf, PackageLocator> {
+        &self.location_map
+    }
+
+    /// Returns all the packages whose location contains the given path, from
+    /// the most specific one to the least specific one. Unlike `find_locator`,
+    /// virtual packages and ignored paths aren't treated specially.
+    pub fn packages_at_path<'a>(
+        &'a self,
+        path: &Path,
+    ) -> Vec<(&'a PackageLocator, &'a PackageInformation)> {
+        self.location_trie
+            .get_all_ancestor_values(&path)
+            .into_iter()
+            .filter_map(|locator| Some((locator, self.get_package_information(locator)?)))
+            .collect()
+    }
+
+    pub fn is_any_version_installed(&self, name: &str) -> bool {
+        self.package_registry_data.get(name).is_some_and(|references| !references.is_empty())
+    }
+
+    /// Returns the dependency the fallback pool provides for the given name,
+    /// if any. Names explicitly mapped to `None` also return `None`.
+    pub fn fallback_for(&self, name: &str) -> Option<&PackageDependency> {
+        self.fallback_pool.get(name).and_then(|dependency| dependency.as_ref())
+    }
+
+    pub fn is_excluded_from_fallback(&self, locator: &PackageLocator) -> bool {
+        self.fallback_exclusion_list
+            .get(&locator.name)
+            .is_some_and(|references| references.contains(&locator.reference))
+    }
+
+    pub fn is_virtual_package(&self, locator: &PackageLocator) -> bool {
+        locator.is_virtual()
+    }
+
+    /// Returns the number of packages whose location is stored within a zip
+    /// archive.
+    pub fn zip_backed_package_count(&self) -> usize {
+        self.zip_backed_packages().count()
+    }
+
+    /// Returns a stable hexadecimal hash of the given package, computed from
+    /// its location relative to the project root and from its dependencies
+    /// and peers. The hash doesn't depend on the order in which they are
+    /// declared, nor on where the project is located on disk, so it can be
+    /// used as a cache key across machines. Fails if the package isn't part
+    /// of the registry.
+    pub fn generate_package_hash(&self, locator: &PackageLocator) -> Result<String, Error> {
+        let info = self.get_package_information(locator).ok_or_else(|| {
+            Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+                message: format!(
+                    "The package registry doesn't contain {}@{}, so it can't be hashed.",
+                    locator.name, locator.reference
+                ),
+                manifest_path: self.manifest_path.clone(),
+                source: None,
+            }))
+        })?;
+
+        let relative_location = self.relative_to_root(&info.package_location);
+        let portable_location = relative_location
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut dependencies = info.package_dependencies.iter().collect::<Vec<_>>();
+        dependencies.sort_by_key(|(name, _)| *name);
+
+        let mut peers = info.package_peers.iter().collect::<Vec<_>>();
+        peers.sort();
+
+        // The `Hash` implementations of the std types aren't guaranteed to be
+        // the same across platforms and Rust releases, so only explicit bytes
+        // are fed to the hasher; strings are prefixed by their length
+        fn write_str(hasher: &mut SipHasher13, value: &str) {
+            hasher.write(&(value.len() as u64).to_le_bytes());
+            hasher.write(value.as_bytes());
+        }
+
+        let mut hasher = SipHasher13::new();
+
+        write_str(&mut hasher, &portable_location);
+
+        hasher.write(&(dependencies.len() as u64).to_le_bytes());
+        for (name, dependency) in dependencies {
+            write_str(&mut hasher, name);
+            match dependency {
+                None => hasher.write(&[0]),
+                Some(PackageDependency::Reference(reference)) => {
+                    hasher.write(&[1]);
+                    write_str(&mut hasher, reference);
+                }
+                Some(PackageDependency::Alias(name, reference)) => {
+                    hasher.write(&[2]);
+                    write_str(&mut hasher, name);
+                    write_str(&mut hasher, reference);
+                }
+            }
+        }
+
+        hasher.write(&(peers.len() as u64).to_le_bytes());
+        for peer in peers {
+            write_str(&mut hasher, peer);
+        }
+
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+}
+
+// The manifest path, its directory, and the location lookups are all derived
+// from where the manifest was loaded from, so they don't take part in the
+// comparison; the package locations are compared relative to the manifest
+// directory for the same reason
+impl PartialEq for Manifest {
+    fn eq(&self, other: &Self) -> bool {
+        self.metadata == other.metadata
+            && self.enable_top_level_fallback == other.enable_top_level_fallback
+            && self.ignore_pattern_data == other.ignore_pattern_data
+            && self.dependency_tree_roots == other.dependency_tree_roots
+            && self.fallback_pool == other.fallback_pool
+            && self.fallback_exclusion_list == other.fallback_exclusion_list
+            && self.package_registry_data.len() == other.package_registry_data.len()
+            && self.package_registry_data.iter().all(|(name, references)| {
+                other.package_registry_data.get(name).is_some_and(|other_references| {
+                    references.len() == other_references.len()
+                        && references.iter().all(|(reference, info)| {
+                            other_references.get(reference).is_some_and(|other_info| {
+                                self.package_information_eq(info, other, other_info)
+                            })
+                        })
+                })
+            })
+    }
+}
+
+impl Manifest {
+    fn package_information_eq(
+        &self,
+        info: &PackageInformation,
+        other: &Manifest,
+        other_info: &PackageInformation,
+    ) -> bool {
+        let relative_location = |manifest: &Manifest, info: &PackageInformation| {
+            pathdiff::diff_paths(&info.package_location, &manifest.manifest_dir)
+                .unwrap_or_else(|| info.package_location.clone())
+        };
+
+        info.discard_from_lookup == other_info.discard_from_lookup
+            && info.package_dependencies == other_info.package_dependencies
+            && info.package_peers == other_info.package_peers
+            && relative_location(self, info) == relative_location(other, other_info)
+    }
+}
+
+impl Eq for Manifest {}
+
+/// Lists the packages that differ between two versions of a manifest, as
+/// returned by `Manifest::diff`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ManifestDiff<'a, 'b> {
+    pub added: Vec<(PackageLocator, &'b PackageInformation)>,
+    pub removed: Vec<(PackageLocator, &'a PackageInformation)>,
+    pub location_changed: Vec<(PackageLocator, &'a Path, &'b Path)>,
+}
+
+/// Non-fatal issues found while initializing a manifest, as returned by
+/// `init_pnp_manifest_checked`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ManifestWarning {
+    /// The package location isn't valid UTF-8, so its normalized form had to
+    /// replace the invalid sequences and no longer matches the path on disk.
+    NonUtf8PackageLocation { locator: PackageLocator, original_path: PathBuf },
+}
+
+impl ManifestWarning {
+    /// Returns the locator of the package the warning was raised for.
+    pub fn locator(&self) -> &PackageLocator {
+        match self {
+            ManifestWarning::NonUtf8PackageLocation { locator, .. } => locator,
+        }
+    }
+}
+
+/// Iterator over the packages of a manifest, as returned by
+/// `Manifest::iter_packages`.
+pub struct PackageIter<'a> {
+    names: indexmap::map::Iter<'a, String, FxIndexMap<String, PackageInformation>>,
+    current: Option<(&'a String, indexmap::map::Iter<'a, String, PackageInformation>)>,
+    sentinel: bool,
+}
+
+impl<'a> Iterator for PackageIter<'a> {
+    type Item = (PackageLocator, &'a PackageInformation);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((name, references)) = &mut self.current {
+                for (reference, info) in references.by_ref() {
+                    if !self.sentinel && name.is_empty() && reference.is_empty() {
+                        continue;
+                    }
+
+                    let locator =
+                        PackageLocator { name: (*name).clone(), reference: reference.clone() };
+                    return Some((locator, info));
+                }
+            }
+
+            let (name, references) = self.names.next()?;
+            self.current = Some((name, references.iter()));
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Manifest {
+    type Item = (PackageLocator, &'a PackageInformation);
+    type IntoIter = PackageIter<'a>;
+
+    fn into_iter(self) -> PackageIter<'a> {
+        self.iter_packages()
+    }
+}
+
+// Panics on unknown packages, like `get_package` does
+impl Index<&PackageLocator> for Manifest {
+    type Output = PackageInformation;
+
+    fn index(&self, locator: &PackageLocator) -> &PackageInformation {
+        &self[(locator.name.as_str(), locator.reference.as_str())]
+    }
+}
+
+impl Index<(&str, &str)> for Manifest {
+    type Output = PackageInformation;
+
+    fn index(&self, (name, reference): (&str, &str)) -> &PackageInformation {
+        self.package_registry_data
+            .get(name)
+            .and_then(|references| references.get(reference))
+            .unwrap_or_else(|| {
+                panic!("Should have an entry in the package registry for {name}@{reference}")
+            })
+    }
+}
+
+// Unlike the `Debug` output, only prints a short summary of the manifest
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PnP Manifest @ {}", self.manifest_path.display())?;
+        writeln!(
+            f,
+            "  {} packages ({} zip-backed)",
+            self.package_count(),
+            self.zip_backed_package_count()
+        )?;
+        writeln!(f, "  fallback pool: {} entries", self.fallback_pool_size())?;
+        match &self.ignore_pattern_data {
+            Some(regex) => write!(f, "  ignore pattern: {}", regex.0.as_str()),
+            None => write!(f, "  ignore pattern: none"),
+        }
+    }
+}
+
+// The hash only depends on the locator content (consistent with `PartialEq`),
+// so deterministic hashers such as `FxHasher` always produce the same hash
+// for equal locators. The hash values themselves aren't guaranteed to remain
+// the same across processes or versions of this crate.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Ord, PartialOrd, Deserialize, Serialize)]
 pub struct PackageLocator {
     pub name: String,
     pub reference: String,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl PackageLocator {
+    /// Parses a locator from the `name@reference` format used by Yarn (for
+    /// example `@babel/core@npm:7.0.0`). The name ends at the first `@`
+    /// following the scope, as references may themselves contain some.
+    pub fn from_pnp_string(s: &str) -> Result<PackageLocator, BadSpecifier> {
+        let name_start =
+            if s.starts_with('@') { s.find('/').map_or(s.len(), |i| i + 1) } else { 0 };
+
+        match s[name_start..].find('@').map(|i| name_start + i) {
+            Some(separator) if separator > name_start && separator + 1 < s.len() => {
+                Ok(PackageLocator {
+                    name: s[..separator].to_string(),
+                    reference: s[separator + 1..].to_string(),
+                })
+            }
+
+            _ => Err(BadSpecifier {
+                message: String::from("Invalid locator; expected the name@reference format"),
+                specifier: s.to_string(),
+            }),
+        }
+    }
+
+    /// Returns whether the locator refers to a virtual package, which Yarn
+    /// creates for each set of peer dependencies a package is instantiated
+    /// with.
+    pub fn is_virtual(&self) -> bool {
+        self.reference.starts_with("virtual:")
+    }
+
+    /// Returns the path of a workspace relative to the project root, as
+    /// found in its `workspace:` reference (`.` for the root workspace).
+    /// Workspaces living outside the project root start with `..`.
+    pub fn workspace_relative_path(&self) -> Option<&Path> {
+        let path = self.reference.strip_prefix("workspace:")?;
+        let path = path.strip_prefix("./").unwrap_or(path).trim_end_matches('/');
+
+        if path.is_empty() { Some(Path::new(".")) } else { Some(Path::new(path)) }
+    }
+}
+
+// Unlike the manifest serialization, the package location is serialized
+// as-is rather than relative to the manifest directory
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageInformation {
     pub package_location: PathBuf,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub discard_from_lookup: bool,
 
-    #[serde(deserialize_with = "deserialize_package_dependencies")]
-    pub package_dependencies: FxHashMap<String, Option<PackageDependency>>,
+    #[serde(
+        deserialize_with = "deserialize_package_dependencies",
+        serialize_with = "serialize_as_tuples"
+    )]
+    pub package_dependencies: FxIndexMap<String, Option<PackageDependency>>,
+
+    // Only listed for the virtual packages
+    #[serde(default, skip_serializing_if = "FxIndexSet::is_empty")]
+    pub package_peers: FxIndexSet<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl PackageInformation {
+    /// Parses the package location into a `VPath`, which tells whether the
+    /// package is stored within a zip archive or a virtual folder.
+    pub fn resolved_location(&self) -> std::io::Result<VPath> {
+        VPath::from(&self.package_location)
+    }
+
+    pub fn is_zip_backed(&self) -> bool {
+        matches!(self.resolved_location(), Ok(VPath::Zip(_)))
+    }
+
+    /// Returns whether the package declares the given dependency, even if
+    /// it's an unfulfilled peer dependency.
+    pub fn has_dependency(&self, name: &str) -> bool {
+        self.package_dependencies.contains_key(name)
+    }
+
+    /// Iterates over the names of the declared dependencies, including the
+    /// unfulfilled peer dependencies.
+    pub fn dependency_names(&self) -> impl Iterator<Item = &str> {
+        self.package_dependencies.keys().map(String::as_str)
+    }
+
+    pub fn declared_dependency_count(&self) -> usize {
+        self.package_dependencies.len()
+    }
+}
+
+/// Builds a `PackageInformation` without going through the manifest format.
+/// As with deserialized manifests, the location may be relative to the
+/// manifest directory until `init_pnp_manifest` is called.
+#[derive(Clone, Debug)]
+pub struct PackageInformationBuilder {
+    info: PackageInformation,
+}
+
+impl PackageInformationBuilder {
+    pub fn new(package_location: PathBuf) -> PackageInformationBuilder {
+        PackageInformationBuilder {
+            info: PackageInformation {
+                package_location,
+                discard_from_lookup: false,
+                package_dependencies: FxIndexMap::default(),
+                package_peers: FxIndexSet::default(),
+            },
+        }
+    }
+
+    /// Declares a dependency; `None` stands for an unfulfilled peer
+    /// dependency.
+    pub fn add_dependency(&mut self, name: &str, dep: Option<PackageDependency>) -> &mut Self {
+        self.info.package_dependencies.insert(name.to_string(), dep);
+        self
+    }
+
+    pub fn discard_from_lookup(&mut self, discard_from_lookup: bool) -> &mut Self {
+        self.info.discard_from_lookup = discard_from_lookup;
+        self
+    }
+
+    pub fn build(&self) -> PackageInformation {
+        self.info.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum PackageDependency {
     Reference(String),
     Alias(String, String),
 }
 
+impl PackageDependency {
+    /// Returns the locator of the package this dependency points to, given
+    /// the name under which it was declared.
+    pub(crate) fn to_locator(&self, name: &str) -> PackageLocator {
+        match self {
+            PackageDependency::Reference(reference) => {
+                PackageLocator { name: name.to_string(), reference: reference.clone() }
+            }
+            PackageDependency::Alias(name, reference) => {
+                PackageLocator { name: name.clone(), reference: reference.clone() }
+            }
+        }
+    }
+}
+
+// The manifest is serialized in the same format as the one Yarn uses for
+// the data payload of the .pnp.cjs files, so that it can be parsed back.
+impl Serialize for Manifest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Manifest", 7)?;
+        if let Some(metadata) = &self.metadata {
+            state.serialize_field("__metadata", metadata)?;
+        }
+        state.serialize_field("enableTopLevelFallback", &self.enable_top_level_fallback)?;
+        state.serialize_field("ignorePatternData", &self.ignore_pattern_data)?;
+        state.serialize_field("dependencyTreeRoots", &self.dependency_tree_roots)?;
+        state.serialize_field("fallbackPool", &SerializeAsTuples(&self.fallback_pool))?;
+        state.serialize_field(
+            "fallbackExclusionList",
+            &SerializeAsTuples(&self.fallback_exclusion_list),
+        )?;
+        state.serialize_field("packageRegistryData", &SerializePackageRegistryData(self))?;
+        state.end()
+    }
+}
+
+struct SerializeAsTuples<'a, K, V>(&'a FxIndexMap<K, V>);
+
+impl<K: Serialize, V: Serialize> Serialize for SerializeAsTuples<'_, K, V> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+fn serialize_as_tuples<K, V, S>(map: &FxIndexMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    SerializeAsTuples(map).serialize(serializer)
+}
+
+struct SerializePackageRegistryData<'a>(&'a Manifest);
+
+impl Serialize for SerializePackageRegistryData<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let manifest_dir: &Path = &self.0.manifest_dir;
+
+        serializer.collect_seq(self.0.package_registry_data.iter().map(|(name, references)| {
+            let references: Vec<_> = references
+                .iter()
+                .map(|(reference, info)| {
+                    (non_empty(reference), SerializePackageInformation { manifest_dir, info })
+                })
+                .collect();
+
+            (non_empty(name), references)
+        }))
+    }
+}
+
+struct SerializePackageInformation<'a> {
+    manifest_dir: &'a Path,
+    info: &'a PackageInformation,
+}
+
+impl Serialize for SerializePackageInformation<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("PackageInformation", 3)?;
+        state.serialize_field(
+            "packageLocation",
+            &to_portable_location(self.manifest_dir, &self.info.package_location),
+        )?;
+        state.serialize_field(
+            "packageDependencies",
+            &SerializeAsTuples(&self.info.package_dependencies),
+        )?;
+        if !self.info.package_peers.is_empty() {
+            state.serialize_field("packagePeers", &self.info.package_peers)?;
+        }
+        if self.info.discard_from_lookup {
+            state.serialize_field("discardFromLookup", &true)?;
+        }
+        state.end()
+    }
+}
+
+fn non_empty(str: &str) -> Option<&str> {
+    (!str.is_empty()).then_some(str)
+}
+
+// Package locations are stored relative to the manifest directory, always
+// starting with either `./` or `../`, and always ending with a slash
+fn to_portable_location(manifest_dir: &Path, location: &Path) -> String {
+    let rel_path =
+        pathdiff::diff_paths(location, manifest_dir).unwrap_or_else(|| location.to_path_buf());
+
+    let mut location = util::normalize_path(rel_path.to_string_lossy());
+
+    if location == "." {
+        location = String::from("./");
+    } else if location != ".." && !location.starts_with("../") && !location.starts_with('/') {
+        location.insert_str(0, "./");
+    }
+
+    if !location.ends_with('/') {
+        location.push('/');
+    }
+
+    location
+}
+
 fn deserialize_fallback_exclusion_list<'de, D>(
     deserializer: D,
-) -> Result<FxHashMap<String, FxHashSet<String>>, D::Error>
+) -> Result<FxIndexMap<String, FxIndexSet<String>>, D::Error>
 where
     D: Deserializer<'de>,
 {
     #[derive(Debug, Deserialize)]
-    struct Item(String, FxHashSet<String>);
+    struct Item(String, FxIndexSet<String>);
 
-    let mut map = FxHashMap::default();
+    let mut map = FxIndexMap::default();
     for item in Vec::<Item>::deserialize(deserializer)? {
         map.insert(item.0, item.1);
     }
@@ -98,14 +861,14 @@ where
 
 fn deserialize_package_dependencies<'de, D>(
     deserializer: D,
-) -> Result<FxHashMap<String, Option<PackageDependency>>, D::Error>
+) -> Result<FxIndexMap<String, Option<PackageDependency>>, D::Error>
 where
     D: Deserializer<'de>,
 {
     #[derive(Debug, Deserialize)]
     struct Item(String, Option<PackageDependency>);
 
-    let mut map = FxHashMap::default();
+    let mut map = FxIndexMap::default();
     for item in Vec::<Item>::deserialize(deserializer)? {
         map.insert(item.0, item.1);
     }
@@ -118,9 +881,49 @@ fn deserialize_package_registry_data<'de, D>(
 where
     D: Deserializer<'de>,
 {
-    #[derive(Debug, Deserialize)]
     struct Item(Option<String>, Vec<(Option<String>, PackageInformation)>);
 
+    struct ItemVisitor;
+
+    impl<'de> Visitor<'de> for ItemVisitor {
+        type Value = Item;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a package registry entry")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Item, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let name: Option<String> =
+                seq.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+
+            // Errors are wrapped with the package name, otherwise they only
+            // point to a location within a large and minified payload
+            let references = seq
+                .next_element()
+                .map_err(|err| {
+                    A::Error::custom(format!(
+                        "Failed to deserialize package registry entry for `{}`: {err}",
+                        name.as_deref().unwrap_or_default()
+                    ))
+                })?
+                .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+
+            Ok(Item(name, references))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Item {
+        fn deserialize<D>(deserializer: D) -> Result<Item, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_tuple(2, ItemVisitor)
+        }
+    }
+
     let mut map = FxIndexMap::default();
     for item in Vec::<Item>::deserialize(deserializer)? {
         let key = item.0.unwrap_or_default();
@@ -1,14 +1,22 @@
 use fancy_regex::Regex;
-use serde::{Deserialize, Deserializer, de::Error};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use radix_trie::TrieCommon;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
 use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
 
-use std::path::{MAIN_SEPARATOR_STR, Path, PathBuf};
+use std::path::{MAIN_SEPARATOR_STR, Path};
 #[cfg(windows)]
 use std::sync::LazyLock;
 
+/// Maps normalized directory paths to values, answering "which entry
+/// contains this path" lookups.
+///
+/// Lookups aren't allocation-free: the key is normalized into a new string,
+/// and radix_trie then encodes it into a byte buffer of its own.
 #[derive(Debug, Default, Clone)]
 pub struct Trie<T> {
-    inner: radix_trie::Trie<String, (PathBuf, T)>,
+    inner: radix_trie::Trie<String, T>,
 }
 
 impl<T> Trie<T> {
@@ -23,14 +31,32 @@ impl<T> Trie<T> {
     }
 
     pub fn get_ancestor_value<P: AsRef<Path>>(&self, key: &P) -> Option<&T> {
-        self.inner.get_ancestor_value(&self.key(&key)).map(|t| &t.1)
+        self.inner.get_ancestor_value(&self.key(&key))
+    }
+
+    /// Same as `get_ancestor_value`, but also returns the path of the
+    /// matched ancestor.
+    pub fn get_ancestor_entry<P: AsRef<Path>>(&self, key: &P) -> Option<(&Path, &T)> {
+        let ancestor = self.inner.get_ancestor(&self.key(&key))?;
+
+        Some((Path::new(ancestor.key()?), ancestor.value()?))
+    }
+
+    /// Returns the values of all the ancestors of the given path, from the
+    /// closest one to the most distant one.
+    pub fn get_all_ancestor_values<P: AsRef<Path>>(&self, key: &P) -> Vec<&T> {
+        let key = self.key(&key);
+
+        key.match_indices('/')
+            .rev()
+            .filter_map(|(index, _)| self.inner.get(&key[..=index]))
+            .collect()
     }
 
     pub fn insert<P: AsRef<Path>>(&mut self, key: P, value: T) {
         let k = self.key(&key);
-        let p = PathBuf::from(k.clone());
 
-        self.inner.insert(k, (p, value)).map(|t| t.1);
+        self.inner.insert(k, value);
     }
 }
 
@@ -47,7 +73,9 @@ static PORTABLE_PATH_REGEXP: LazyLock<Regex> =
 static UNC_PORTABLE_PATH_REGEXP: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^\/unc\/(\.dot\/)?(.*)$").unwrap());
 
-fn from_portable_path<'a>(str: &'a str) -> Cow<'a, str> {
+/// Turns a portable path (`/C:/foo`, `/unc/server/foo`) back into its native
+/// Windows form. Paths are returned unchanged on other platforms.
+pub fn from_portable_path<'a>(str: &'a str) -> Cow<'a, str> {
     #[cfg(windows)]
     {
         if let Ok(Some(caps)) = PORTABLE_PATH_REGEXP.captures(str) {
@@ -66,7 +94,10 @@ fn from_portable_path<'a>(str: &'a str) -> Cow<'a, str> {
     Cow::Borrowed(str)
 }
 
-fn to_portable_path<'a>(str: &'a str) -> Cow<'a, str> {
+/// Turns a native Windows path (`C:\foo`, `\\server\foo`) into its
+/// portable form, which always starts with a slash. Paths are returned
+/// unchanged on other platforms.
+pub fn to_portable_path<'a>(str: &'a str) -> Cow<'a, str> {
     #[cfg(windows)]
     {
         if let Ok(Some(caps)) = WINDOWS_PATH_REGEXP.captures(str) {
@@ -85,13 +116,31 @@ fn to_portable_path<'a>(str: &'a str) -> Cow<'a, str> {
     Cow::Borrowed(str)
 }
 
+// Device paths (`\\.\pipe\foo`, `\\.\COM3`) don't follow the usual path
+// semantics; turning them into `/unc/.dot/…` paths and back would let `..`
+// segments escape the device namespace, and would mix the separators
+#[cfg(windows)]
+fn is_windows_device_path(str: &str) -> bool {
+    matches!(str.as_bytes(), [b'\\' | b'/', b'\\' | b'/', b'.', b'\\' | b'/', ..])
+}
+
 pub fn normalize_path<P: AsRef<str>>(original: P) -> String {
+    #[cfg(windows)]
+    if is_windows_device_path(original.as_ref()) {
+        return original.as_ref().to_string();
+    }
+
     let original_str = to_portable_path(original.as_ref());
 
     let check_str_root = original_str.strip_prefix('/');
     let str_minus_root = check_str_root.unwrap_or(original_str.as_ref());
 
+    // Backslashes are valid filename characters on Unix, so they are only
+    // treated as separators on Windows
+    #[cfg(windows)]
     let components = str_minus_root.split(&['/', '\\'][..]);
+    #[cfg(not(windows))]
+    let components = str_minus_root.split('/');
 
     let mut out: Vec<&str> = Vec::new();
 
@@ -142,10 +191,143 @@ pub fn normalize_path<P: AsRef<str>>(original: P) -> String {
     from_portable_path(&str).into_owned()
 }
 
+/// Extracts the value of a top-level scalar setting from a `.yarnrc.yml`
+/// file. This only supports the subset of YAML needed for simple settings.
+#[cfg(feature = "yarnrc")]
+pub fn read_yarnrc_setting<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    for line in content.lines() {
+        // Nested keys are indented; comments don't contain settings
+        if line.starts_with([' ', '\t', '#']) {
+            continue;
+        }
+
+        let Some((line_key, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        if line_key.trim_end() != key {
+            continue;
+        }
+
+        let value = value.trim();
+
+        for quote in ['"', '\''] {
+            if let Some(quoted) = value.strip_prefix(quote) {
+                return quoted.split_once(quote).map(|(value, _)| value);
+            }
+        }
+
+        let value = value.split_once(" #").map_or(value, |(value, _)| value).trim_end();
+
+        return (!value.is_empty()).then_some(value);
+    }
+
+    None
+}
+
+// Same set as `encodeURIComponent`, which Yarn uses when encoding references
+const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'!')
+    .remove(b'~')
+    .remove(b'*')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')');
+
+/// Percent-encodes the characters that aren't valid within a URL path
+/// segment, such as the `:` in `npm:4.17.21`.
+pub fn percent_encode_path(s: &str) -> String {
+    utf8_percent_encode(s, PATH_SEGMENT_ENCODE_SET).to_string()
+}
+
+/// Decodes the percent-encoded sequences of the given string. Sequences that
+/// don't decode into valid UTF-8 are replaced by the replacement character.
+pub fn percent_decode_path(s: &str) -> Cow<'_, str> {
+    percent_decode_str(s).decode_utf8_lossy()
+}
+
 #[cfg(test)]
 mod tests {
+    use rstest::rstest;
+
     use super::*;
 
+    #[rstest]
+    #[case("", "")]
+    #[case("foo/bar", "foo/bar")]
+    #[case(r"\/", "/")]
+    #[case(r"foo\/bar\/", "foo/bar/")]
+    #[case(r"\\", r"\\")]
+    #[case(r"\\\/", r"\\/")]
+    #[case(r"\\/", r"\\/")]
+    #[case(r"foo\", r"foo\")]
+    #[case(r"\.", r"\.")]
+    #[case(r"^(?:\.yarn\/cache\/|\\\/tmp)\d+$", r"^(?:\.yarn/cache/|\\/tmp)\d+$")]
+    fn test_strip_slash_escape(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(strip_slash_escape(input), expected);
+    }
+
+    #[rstest]
+    #[case("npm:4.17.21")]
+    #[case("lodash@npm:4.17.21")]
+    #[case("patch:lodash@npm%3A4.17.21#./.yarn/patches/lodash.patch")]
+    #[case("@scope/name")]
+    #[case("with spaces and ?#&=")]
+    #[case("unicode-ünïcødé-日本語")]
+    #[case("")]
+    fn test_percent_encoding_roundtrip(#[case] input: &str) {
+        let encoded = percent_encode_path(input);
+
+        assert!(!encoded.contains([':', '/', ' ', '#', '?']), "{encoded}");
+        assert_eq!(percent_decode_path(&encoded), input);
+    }
+
+    #[test]
+    fn test_percent_decode_path() {
+        assert_eq!(percent_encode_path("npm:4.17.21"), "npm%3A4.17.21");
+        assert_eq!(percent_decode_path("patch:lodash@npm%3A4.17.21"), "patch:lodash@npm:4.17.21");
+        assert!(matches!(percent_decode_path("npm:4.17.21"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_regex_def_eq() {
+        let regex = |pattern: &str| RegexDef(pattern.parse().unwrap());
+
+        assert_eq!(regex("^foo$"), regex("^foo$"));
+        assert_ne!(regex("^foo$"), regex("^bar$"));
+
+        let hash = |regex: &RegexDef| {
+            let mut hasher = rustc_hash::FxHasher::default();
+            regex.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        assert_eq!(hash(&regex("^foo$")), hash(&regex("^foo$")));
+        assert_ne!(hash(&regex("^foo$")), hash(&regex("^bar$")));
+    }
+
+    #[test]
+    fn test_get_all_ancestor_values() {
+        let mut trie = Trie::default();
+
+        trie.insert("/project/", "root");
+        trie.insert("/project/node_modules/a/", "a");
+        trie.insert("/project/node_modules/a/node_modules/b/", "b");
+        trie.insert("/project/node_modules/a/node_modules/bb/", "bb");
+        trie.insert("/project/node_modules/c/", "c");
+
+        assert_eq!(
+            trie.get_all_ancestor_values(&"/project/node_modules/a/node_modules/b/lib/index.js"),
+            vec![&"b", &"a", &"root"]
+        );
+        assert_eq!(trie.get_all_ancestor_values(&"/project/node_modules/a"), vec![&"a", &"root"]);
+        assert_eq!(trie.get_all_ancestor_values(&"/project/node_modules/ab/"), vec![&"root"]);
+        assert!(trie.get_all_ancestor_values(&"/elsewhere/").is_empty());
+    }
+
     #[test]
     fn test_normalize_path() {
         assert_eq!(normalize_path(""), ".");
@@ -169,6 +351,11 @@ mod tests {
         assert_eq!(normalize_path("/../foo/bar//"), "/foo/bar/");
         assert_eq!(normalize_path("/foo/bar/"), "/foo/bar/");
 
+        #[cfg(not(windows))]
+        assert_eq!(normalize_path("foo\\bar"), "foo\\bar");
+        #[cfg(windows)]
+        assert_eq!(normalize_path("foo\\bar"), "foo/bar");
+
         #[cfg(windows)]
         assert_eq!(normalize_path("D:\\foo\\..\\bar"), "D:/bar");
         #[cfg(windows)]
@@ -181,9 +368,58 @@ mod tests {
             "C:/bar/test"
         );
     }
+
+    #[cfg(windows)]
+    #[rstest]
+    #[case("C:\\foo\\bar", "/C:\\foo\\bar")]
+    #[case("C:/foo/bar", "/C:/foo/bar")]
+    #[case("\\\\server\\share", "/unc/server\\share")]
+    #[case("\\\\.\\pipe\\foo", "/unc/.dot/pipe\\foo")]
+    fn test_portable_path_conversion(#[case] native: &str, #[case] portable: &str) {
+        assert_eq!(to_portable_path(native), portable);
+        assert_eq!(from_portable_path(portable).replace('/', "\\"), native.replace('/', "\\"));
+    }
+
+    #[cfg(not(windows))]
+    #[rstest]
+    #[case("/foo/bar")]
+    #[case("C:\\foo")]
+    #[case("/C:/foo")]
+    fn test_portable_path_conversion(#[case] path: &str) {
+        assert!(matches!(to_portable_path(path), Cow::Borrowed(p) if p == path));
+        assert!(matches!(from_portable_path(path), Cow::Borrowed(p) if p == path));
+    }
+
+    #[cfg(windows)]
+    #[rstest]
+    #[case("\\\\.\\pipe\\foo")]
+    #[case("\\\\.\\pipe\\foo\\..\\bar")]
+    #[case("\\\\.\\COM3")]
+    #[case("//./pipe/foo")]
+    fn test_normalize_windows_device_path(#[case] input: &str) {
+        assert_eq!(normalize_path(input), input);
+    }
+
+    #[cfg(feature = "yarnrc")]
+    #[test]
+    fn test_read_yarnrc_setting() {
+        let content = "nodeLinker: pnp\npnpDataPath: ./a.cjs\n";
+        assert_eq!(read_yarnrc_setting(content, "pnpDataPath"), Some("./a.cjs"));
+        assert_eq!(read_yarnrc_setting(content, "nodeLinker"), Some("pnp"));
+        assert_eq!(read_yarnrc_setting(content, "pnpMode"), None);
+
+        assert_eq!(
+            read_yarnrc_setting("pnpDataPath: \"./a b.cjs\" # c", "pnpDataPath"),
+            Some("./a b.cjs")
+        );
+        assert_eq!(read_yarnrc_setting("pnpDataPath: './a.cjs'", "pnpDataPath"), Some("./a.cjs"));
+        assert_eq!(read_yarnrc_setting("pnpDataPath: ./a.cjs # c", "pnpDataPath"), Some("./a.cjs"));
+        assert_eq!(read_yarnrc_setting("foo:\n  pnpDataPath: ./a.cjs", "pnpDataPath"), None);
+        assert_eq!(read_yarnrc_setting("# pnpDataPath: ./a.cjs", "pnpDataPath"), None);
+    }
 }
 
-fn strip_slash_escape(str: &str) -> String {
+pub(crate) fn strip_slash_escape(str: &str) -> String {
     let mut res = String::default();
     res.reserve_exact(str.len());
 
@@ -223,3 +459,27 @@ impl<'de> Deserialize<'de> for RegexDef {
         }
     }
 }
+
+impl Serialize for RegexDef {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+// Two regexes are considered equal when they come from the same pattern
+impl PartialEq for RegexDef {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for RegexDef {}
+
+impl Hash for RegexDef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_str().hash(state);
+    }
+}
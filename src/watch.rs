@@ -0,0 +1,255 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+
+use notify::{PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{Error, FailedManifestHydration, Manifest, load_pnp_manifest};
+
+type EventSender = mpsc::Sender<notify::Result<notify::Event>>;
+
+/// Changes closer to each other than this are reported only once, as Yarn
+/// may write the manifest more than once during a single install.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(50);
+
+/// Watches a PnP manifest and calls the provided callback with the new
+/// manifest each time the file changes. Changes that lead to an invalid
+/// manifest (for example while the file is being written) are ignored.
+pub struct PnpManifestWatcher {
+    watcher: Mutex<Option<Box<dyn Watcher + Send>>>,
+}
+
+impl PnpManifestWatcher {
+    /// Watches the manifest using the native file watching API of the
+    /// platform, or by polling the file on platforms that don't have one.
+    pub fn new<F>(manifest_path: PathBuf, on_change: F) -> Result<PnpManifestWatcher, Error>
+    where
+        F: Fn(Manifest) + Send + 'static,
+    {
+        PnpManifestWatcher::start(manifest_path, on_change, |handler| {
+            Ok(Box::new(RecommendedWatcher::new(handler, notify::Config::default())?))
+        })
+    }
+
+    /// Watches the manifest by checking its content at the given interval,
+    /// which also works on network file systems. The content is compared
+    /// rather than the modification time, as the latter only has a
+    /// one-second precision.
+    pub fn with_polling<F>(
+        manifest_path: PathBuf,
+        interval: Duration,
+        on_change: F,
+    ) -> Result<PnpManifestWatcher, Error>
+    where
+        F: Fn(Manifest) + Send + 'static,
+    {
+        PnpManifestWatcher::start(manifest_path, on_change, |handler| {
+            let config =
+                notify::Config::default().with_poll_interval(interval).with_compare_contents(true);
+
+            Ok(Box::new(PollWatcher::new(handler, config)?))
+        })
+    }
+
+    /// Stops watching the manifest. The callback won't be called anymore once
+    /// this function returns, except for a change that was already being
+    /// processed.
+    pub fn stop(&self) {
+        self.watcher.lock().unwrap().take();
+    }
+
+    fn start<F, W>(
+        manifest_path: PathBuf,
+        on_change: F,
+        create_watcher: W,
+    ) -> Result<PnpManifestWatcher, Error>
+    where
+        F: Fn(Manifest) + Send + 'static,
+        W: FnOnce(EventSender) -> Result<Box<dyn Watcher + Send>, notify::Error>,
+    {
+        let to_error = |err: notify::Error| {
+            Error::FailedManifestHydration(Box::new(FailedManifestHydration {
+                message: String::from("We failed to watch the manifest for changes."),
+                manifest_path: manifest_path.clone(),
+                source: Some(Arc::new(err)),
+            }))
+        };
+
+        let watched_path = manifest_path.canonicalize().map_err(|err| to_error(err.into()))?;
+
+        // The parent directory is watched rather than the file itself, since
+        // the manifest is usually replaced rather than modified in place
+        let watched_dir = watched_path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let (sender, receiver) = mpsc::channel();
+
+        let mut watcher = create_watcher(sender).map_err(to_error)?;
+        watcher.watch(&watched_dir, RecursiveMode::NonRecursive).map_err(to_error)?;
+
+        // The thread stops once the watcher (and thus the sender) is dropped
+        thread::spawn(move || process_events(receiver, &watched_path, on_change));
+
+        Ok(PnpManifestWatcher { watcher: Mutex::new(Some(watcher)) })
+    }
+}
+
+fn process_events<F>(
+    receiver: mpsc::Receiver<notify::Result<notify::Event>>,
+    watched_path: &Path,
+    on_change: F,
+) where
+    F: Fn(Manifest),
+{
+    while let Ok(event) = receiver.recv() {
+        // Reading the manifest emits access events, which mustn't trigger
+        // another reload
+        let is_relevant = event.is_ok_and(|event| {
+            !event.kind.is_access() && event.paths.iter().any(|path| path == watched_path)
+        });
+
+        if !is_relevant {
+            continue;
+        }
+
+        loop {
+            match receiver.recv_timeout(DEBOUNCE_DELAY) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        if let Ok(manifest) = load_pnp_manifest(watched_path) {
+            on_change(manifest);
+        }
+    }
+}
+
+impl Drop for PnpManifestWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use notify::{
+        Event, EventKind,
+        event::{AccessKind, ModifyKind},
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn watched_manifest() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+
+        let manifest_path = dir.path().join(".pnp.cjs");
+        fs::copy("data/pnp-yarn-v4.cjs", &manifest_path).unwrap();
+
+        (dir, manifest_path)
+    }
+
+    // Only checks that the change gets reported at all; the debouncing is
+    // tested separately, as it can't be made deterministic with actual writes
+    fn assert_notified(manifest_path: &Path, receiver: &mpsc::Receiver<Manifest>) {
+        fs::copy("data/pnp-yarn-v4-registry-data-order.cjs", manifest_path).unwrap();
+
+        let manifest = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(manifest.package_count(), 6);
+    }
+
+    #[test]
+    fn test_watch_manifest() {
+        let (_dir, manifest_path) = watched_manifest();
+
+        let (sender, receiver) = mpsc::channel();
+        let watcher = PnpManifestWatcher::new(manifest_path.clone(), move |manifest| {
+            let _ = sender.send(manifest);
+        })
+        .unwrap();
+
+        assert_notified(&manifest_path, &receiver);
+
+        watcher.stop();
+        fs::copy("data/pnp-yarn-v4.cjs", &manifest_path).unwrap();
+
+        // The callback is dropped along with the watcher thread, which closes
+        // the channel; nothing written after `stop` may have been reported
+        loop {
+            match receiver.recv_timeout(Duration::from_secs(5)) {
+                Ok(manifest) => assert_eq!(manifest.package_count(), 6),
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => panic!("Expected the watcher thread to stop"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_watch_manifest_with_polling() {
+        let (_dir, manifest_path) = watched_manifest();
+
+        let (sender, receiver) = mpsc::channel();
+        let _watcher = PnpManifestWatcher::with_polling(
+            manifest_path.clone(),
+            Duration::from_millis(10),
+            move |manifest| {
+                let _ = sender.send(manifest);
+            },
+        )
+        .unwrap();
+
+        assert_notified(&manifest_path, &receiver);
+    }
+
+    #[test]
+    fn test_process_events_debounces_changes() {
+        let (_dir, manifest_path) = watched_manifest();
+
+        let (event_sender, event_receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::channel();
+
+        // The events are all queued before being processed, so the changes
+        // necessarily fall within the same debounce window
+        event_sender
+            .send(
+                Ok(Event::new(EventKind::Access(AccessKind::Any)).add_path(manifest_path.clone())),
+            )
+            .unwrap();
+        for _ in 0..3 {
+            event_sender
+                .send(Ok(
+                    Event::new(EventKind::Modify(ModifyKind::Any)).add_path(manifest_path.clone())
+                ))
+                .unwrap();
+        }
+
+        let events_thread = thread::spawn(move || {
+            process_events(event_receiver, &manifest_path, move |manifest| {
+                let _ = sender.send(manifest);
+            })
+        });
+
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        drop(event_sender);
+        events_thread.join().unwrap();
+
+        assert!(receiver.recv().is_err());
+    }
+
+    #[test]
+    fn test_watch_missing_manifest() {
+        let result = PnpManifestWatcher::new(PathBuf::from("/does/not/exist/.pnp.cjs"), |_| {});
+
+        assert!(matches!(result, Err(Error::FailedManifestHydration(_))));
+    }
+}
@@ -1,10 +1,12 @@
 use std::{
-    error::Error,
-    io::{Cursor, Read},
+    borrow::Cow,
+    fmt,
+    io::{Cursor, Read, Write},
+    path::Path,
 };
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use flate2::read::DeflateDecoder;
+use flate2::{Crc, read::DeflateDecoder, write::DeflateEncoder};
 use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::fs::FileType;
@@ -21,9 +23,79 @@ pub struct Entry {
     pub compression: Compression,
     pub offset: usize,
     pub size: usize,
+    pub crc32: u32,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ZipError {
+    #[error("End of central directory record not found")]
+    EndOfCentralDirectoryNotFound,
+
+    #[error(transparent)]
+    Utf8Error(#[from] std::str::Utf8Error),
+
+    #[error("Invalid signature {0:#010x}")]
+    InvalidSignature(u32),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    #[error("Unsupported compression method {0}")]
+    UnsupportedCompression(u16),
+
+    #[error("Integrity check failed for {} entries", .0.len())]
+    IntegrityCheckFailed(Vec<(String, IntegrityError)>),
+}
+
+/// Archives that can't be read are reported as `InvalidData` errors (or with
+/// the kind of the underlying I/O error), wrapping the `ZipError` so that it
+/// can be recovered through `get_ref`.
+impl From<ZipError> for std::io::Error {
+    fn from(err: ZipError) -> std::io::Error {
+        let kind = match &err {
+            ZipError::IoError(err) => err.kind(),
+            _ => std::io::ErrorKind::InvalidData,
+        };
+
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// Why an entry failed the integrity check, as returned by
+/// `Zip::verify_integrity`.
+#[derive(thiserror::Error, Debug)]
+pub enum IntegrityError {
+    #[error("Checksum mismatch (expected {expected:#010x}, got {actual:#010x})")]
+    CrcMismatch { expected: u32, actual: u32 },
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// Options for `Zip::new_with_options`.
+#[derive(Clone, Debug, Default)]
+pub struct ZipOpenOptions {
+    /// Whether to skip `Zip::verify_integrity` when opening the archive.
+    /// Checking requires decompressing every entry, so it's best left to
+    /// archives coming from untrusted sources.
+    pub skip_integrity_check: bool,
+}
+
+/// A direct child of a directory of an archive, as returned by `Zip::read_dir`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZipDirent<'a> {
+    pub name: &'a str,
+    pub file_type: FileType,
+}
+
+/// Lists the files that differ between two versions of an archive.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ZipDiff<'a, 'b> {
+    pub added: Vec<&'b str>,
+    pub removed: Vec<&'a str>,
+    pub modified: Vec<(&'a str, &'b str)>,
 }
 
-#[derive(Debug)]
 pub struct Zip<T>
 where
     T: AsRef<[u8]>,
@@ -31,14 +103,31 @@ where
     storage: T,
     pub files: FxHashMap<String, Entry>,
     pub dirs: FxHashSet<String>,
+    comment: Option<String>,
+}
+
+// Only the entry counts are printed, as the archive content can be large
+impl<T> fmt::Debug for Zip<T>
+where
+    T: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Zip")
+            .field("files", &self.files.len())
+            .field("dirs", &self.dirs.len())
+            .finish()
+    }
 }
 
 impl<T> Zip<T>
 where
     T: AsRef<[u8]>,
 {
-    pub fn new(storage: T) -> Result<Zip<T>, Box<dyn Error>> {
-        let mut zip = Zip { storage, files: Default::default(), dirs: Default::default() };
+    pub fn new(storage: T) -> Result<Zip<T>, ZipError> {
+        let mut zip =
+            Zip { storage, files: Default::default(), dirs: Default::default(), comment: None };
+
+        zip.comment = read_archive_comment(zip.storage.as_ref())?;
 
         for (name, maybe_entry) in list_zip_entries(zip.storage.as_ref())? {
             let name = util::normalize_path(name);
@@ -59,6 +148,53 @@ where
         Ok(zip)
     }
 
+    /// Same as `new`, but also checks the integrity of the archive unless
+    /// told otherwise.
+    pub fn new_with_options(storage: T, options: &ZipOpenOptions) -> Result<Zip<T>, ZipError> {
+        let zip = Zip::new(storage)?;
+
+        if !options.skip_integrity_check {
+            zip.verify_integrity().map_err(ZipError::IntegrityCheckFailed)?;
+        }
+
+        Ok(zip)
+    }
+
+    /// Decompresses all the files of the archive and compares their checksum
+    /// against the one stored in the central directory. The failing entries
+    /// are returned sorted by name.
+    pub fn verify_integrity(&self) -> Result<(), Vec<(String, IntegrityError)>> {
+        let mut failures = Vec::new();
+
+        for (name, entry) in &self.files {
+            let data = match self.read(name) {
+                Ok(data) => data,
+                Err(err) => {
+                    failures.push((name.clone(), IntegrityError::IoError(err)));
+                    continue;
+                }
+            };
+
+            let mut crc = Crc::new();
+            crc.update(&data);
+
+            if crc.sum() != entry.crc32 {
+                failures.push((
+                    name.clone(),
+                    IntegrityError::CrcMismatch { expected: entry.crc32, actual: crc.sum() },
+                ));
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        failures.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        Err(failures)
+    }
+
     pub fn file_type(&self, p: &str) -> Result<FileType, std::io::Error> {
         if self.is_dir(p) {
             Ok(FileType::Directory)
@@ -69,6 +205,93 @@ where
         }
     }
 
+    /// Returns whether the path is either a file or a directory of the
+    /// archive.
+    #[inline]
+    pub fn contains(&self, p: &str) -> bool {
+        self.contains_file(p) || self.contains_dir(p)
+    }
+
+    #[inline]
+    pub fn contains_file(&self, p: &str) -> bool {
+        self.files.contains_key(p)
+    }
+
+    #[inline]
+    pub fn contains_dir(&self, p: &str) -> bool {
+        self.is_dir(p)
+    }
+
+    /// Returns the comment of the archive, if it has one that's valid UTF-8.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Iterates over the direct children of a directory, in no particular
+    /// order. The children are looked up as the iterator is consumed, rather
+    /// than collected upfront. An empty path refers to the archive root.
+    pub fn read_dir<'a>(
+        &'a self,
+        dir: &str,
+    ) -> Result<impl Iterator<Item = ZipDirent<'a>> + 'a, std::io::Error> {
+        let prefix = match dir.trim_end_matches('/') {
+            "" => String::new(),
+            dir => format!("{dir}/"),
+        };
+
+        if !prefix.is_empty() && !self.dirs.contains(&prefix) {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotFound));
+        }
+
+        let files = self.files.keys().map(|name| (name, FileType::File));
+        let dirs = self.dirs.iter().map(|name| (name, FileType::Directory));
+
+        Ok(files.chain(dirs).filter_map(move |(path, file_type)| {
+            let name = path.strip_prefix(prefix.as_str())?;
+            let name =
+                if file_type == FileType::Directory { name.strip_suffix('/')? } else { name };
+
+            (!name.is_empty() && !name.contains('/')).then_some(ZipDirent { name, file_type })
+        }))
+    }
+
+    /// Returns the number of files in the archive, directories excluded.
+    pub fn entry_count(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn dir_count(&self) -> usize {
+        self.dirs.len()
+    }
+
+    pub fn total_count(&self) -> usize {
+        self.entry_count() + self.dir_count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_count() == 0
+    }
+
+    // Corrupted archives may point past the end of the storage
+    fn entry_data(&self, entry: &Entry) -> Result<&[u8], std::io::Error> {
+        entry
+            .offset
+            .checked_add(entry.size)
+            .and_then(|end| self.storage.as_ref().get(entry.offset..end))
+            .ok_or(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+    }
+
+    // The hash tables' own overhead isn't accounted for
+    fn index_memory_usage(&self) -> usize {
+        let files: usize = self.files.keys().map(String::len).sum();
+        let dirs: usize = self.dirs.iter().map(String::len).sum();
+
+        files
+            + self.files.len() * std::mem::size_of::<(String, Entry)>()
+            + dirs
+            + self.dirs.len() * std::mem::size_of::<String>()
+    }
+
     fn is_dir(&self, p: &str) -> bool {
         if p.ends_with('/') { self.dirs.contains(p) } else { self.dirs.contains(&format!("{p}/")) }
     }
@@ -76,8 +299,7 @@ where
     pub fn read(&self, p: &str) -> Result<Vec<u8>, std::io::Error> {
         let entry = self.files.get(p).ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?;
 
-        let data = self.storage.as_ref();
-        let slice = &data[entry.offset..entry.offset + entry.size];
+        let slice = self.entry_data(entry)?;
 
         match entry.compression {
             Compression::Deflate => {
@@ -95,10 +317,236 @@ where
     }
 
     pub fn read_to_string(&self, p: &str) -> Result<String, std::io::Error> {
-        let data = self.read(p)?;
+        self.read_to_str(p).map(Cow::into_owned)
+    }
+
+    /// Same as `read_to_string`, but borrows the content directly from the
+    /// archive when the entry isn't compressed.
+    pub fn read_to_str<'a>(&'a self, p: &str) -> Result<Cow<'a, str>, std::io::Error> {
+        let entry = self.files.get(p).ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?;
+
+        match entry.compression {
+            Compression::Uncompressed => {
+                Ok(Cow::Borrowed(io_bytes_to_str(self.entry_data(entry)?)?))
+            }
+
+            Compression::Deflate => {
+                let data = self.read(p)?;
+
+                Ok(Cow::Owned(String::from_utf8(data).map_err(|_| make_io_utf8_error())?))
+            }
+        }
+    }
+
+    /// Compares the files of two archives. Files present in both are reported
+    /// as modified when their checksum or size differ.
+    pub fn diff<'a, 'b, U>(old: &'a Zip<T>, new: &'b Zip<U>) -> ZipDiff<'a, 'b>
+    where
+        U: AsRef<[u8]>,
+    {
+        let mut diff = ZipDiff::default();
+
+        for (name, old_entry) in &old.files {
+            match new.files.get_key_value(name) {
+                None => diff.removed.push(name.as_str()),
+                Some((new_name, new_entry)) => {
+                    if old_entry.crc32 != new_entry.crc32 || old_entry.size != new_entry.size {
+                        diff.modified.push((name.as_str(), new_name.as_str()));
+                    }
+                }
+            }
+        }
+
+        for name in new.files.keys() {
+            if !old.files.contains_key(name) {
+                diff.added.push(name.as_str());
+            }
+        }
+
+        diff.added.sort_unstable();
+        diff.removed.sort_unstable();
+        diff.modified.sort_unstable();
+
+        diff
+    }
+}
+
+impl Zip<Vec<u8>> {
+    /// Estimates the memory used by the archive, in bytes, including its
+    /// content.
+    pub fn memory_usage(&self) -> usize {
+        self.storage.len() + self.index_memory_usage()
+    }
+
+    /// Builds an uncompressed in-memory archive out of the files stored
+    /// within the given directory, as if it had been zipped.
+    pub fn from_directory(root: &Path) -> Result<Zip<Vec<u8>>, std::io::Error> {
+        let mut zip = Zip {
+            storage: Vec::new(),
+            files: Default::default(),
+            dirs: Default::default(),
+            comment: None,
+        };
+
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            for dir_entry in std::fs::read_dir(&dir)? {
+                let path = dir_entry?.path();
+
+                let rel_path = path.strip_prefix(root).expect("Should be a child of the root");
+                let name = util::normalize_path(rel_path.to_string_lossy());
+
+                if path.is_dir() {
+                    zip.dirs.insert(name + "/");
+                    pending.push(path);
+                } else {
+                    let data = std::fs::read(&path)?;
+
+                    let mut crc = Crc::new();
+                    crc.update(&data);
+
+                    zip.files.insert(
+                        name,
+                        Entry {
+                            compression: Compression::Uncompressed,
+                            offset: zip.storage.len(),
+                            size: data.len(),
+                            crc32: crc.sum(),
+                        },
+                    );
+
+                    zip.storage.extend(data);
+                }
+            }
+        }
+
+        Ok(zip)
+    }
+}
+
+impl Zip<&[u8]> {
+    /// Estimates the memory used by the archive, in bytes. The content is
+    /// borrowed, so only the entry index is accounted for.
+    pub fn memory_usage(&self) -> usize {
+        self.index_memory_usage()
+    }
+}
+
+/// Creates archives in memory, which can then be read through `Zip::new`.
+/// Timestamps are all set to 1980-01-01, so the output only depends on the
+/// entries and the order in which they're added.
+#[derive(Debug, Default)]
+pub struct ZipBuilder {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entry_count: u16,
+}
+
+impl ZipBuilder {
+    pub fn new() -> ZipBuilder {
+        ZipBuilder::default()
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8], compression: Compression) -> &mut Self {
+        let mut crc = Crc::new();
+        crc.update(data);
+
+        let (compression_method, compressed_data) = match compression {
+            Compression::Uncompressed => (0, Cow::Borrowed(data)),
+            Compression::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).expect("Writing to a Vec shouldn't fail");
+
+                (8, Cow::Owned(encoder.finish().expect("Writing to a Vec shouldn't fail")))
+            }
+        };
+
+        self.add_entry(name, compression_method, crc.sum(), &compressed_data, data.len());
+        self
+    }
+
+    pub fn add_dir(&mut self, name: &str) -> &mut Self {
+        let name = if name.ends_with('/') { name.to_string() } else { format!("{name}/") };
+
+        self.add_entry(&name, 0, 0, &[], 0);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut data = self.data;
+
+        let central_directory_offset = to_u32(data.len());
+        let central_directory_size = to_u32(self.central_directory.len());
+
+        data.extend_from_slice(&self.central_directory);
+
+        data.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes()); // number of this disk
+        data.extend_from_slice(&0u16.to_le_bytes()); // disk where the central directory starts
+        data.extend_from_slice(&self.entry_count.to_le_bytes());
+        data.extend_from_slice(&self.entry_count.to_le_bytes());
+        data.extend_from_slice(&central_directory_size.to_le_bytes());
+        data.extend_from_slice(&central_directory_offset.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes()); // comment length
 
-        Ok(io_bytes_to_str(data.as_slice())?.to_string())
+        data
     }
+
+    fn add_entry(
+        &mut self,
+        name: &str,
+        compression_method: u16,
+        crc32: u32,
+        compressed_data: &[u8],
+        uncompressed_size: usize,
+    ) {
+        // 1980-01-01 00:00:00, the earliest date that can be represented
+        const DOS_TIME: u16 = 0;
+        const DOS_DATE: u16 = (1 << 5) | 1;
+
+        let local_header_offset = to_u32(self.data.len());
+        let name_length: u16 = name.len().try_into().expect("Entry names are limited to 64KiB");
+        let is_dir = name.ends_with('/');
+
+        self.entry_count = self.entry_count.checked_add(1).expect("Too many entries");
+
+        let fields = |buf: &mut Vec<u8>| {
+            buf.extend_from_slice(&20u16.to_le_bytes()); // version needed to extract
+            buf.extend_from_slice(&0u16.to_le_bytes()); // general purpose bit flag
+            buf.extend_from_slice(&compression_method.to_le_bytes());
+            buf.extend_from_slice(&DOS_TIME.to_le_bytes());
+            buf.extend_from_slice(&DOS_DATE.to_le_bytes());
+            buf.extend_from_slice(&crc32.to_le_bytes());
+            buf.extend_from_slice(&to_u32(compressed_data.len()).to_le_bytes());
+            buf.extend_from_slice(&to_u32(uncompressed_size).to_le_bytes());
+            buf.extend_from_slice(&name_length.to_le_bytes());
+            buf.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        };
+
+        self.data.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        fields(&mut self.data);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(compressed_data);
+
+        let central_directory = &mut self.central_directory;
+        central_directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central_directory.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        fields(central_directory);
+        central_directory.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central_directory.extend_from_slice(&0u16.to_le_bytes()); // disk number start
+        central_directory.extend_from_slice(&0u16.to_le_bytes()); // internal file attributes
+        // MS-DOS directory attribute
+        let external_file_attributes: u32 = if is_dir { 0x10 } else { 0 };
+        central_directory.extend_from_slice(&external_file_attributes.to_le_bytes());
+        central_directory.extend_from_slice(&local_header_offset.to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+    }
+}
+
+// Zip64 isn't supported, so the archive must stay below 4GiB
+fn to_u32(value: usize) -> u32 {
+    value.try_into().expect("Archives are limited to 4GiB")
 }
 
 fn io_bytes_to_str(vec: &[u8]) -> Result<&str, std::io::Error> {
@@ -109,7 +557,7 @@ fn make_io_utf8_error() -> std::io::Error {
     std::io::Error::new(std::io::ErrorKind::InvalidData, "File did not contain valid UTF-8")
 }
 
-pub fn list_zip_entries(data: &[u8]) -> Result<FxHashMap<String, Option<Entry>>, Box<dyn Error>> {
+pub fn list_zip_entries(data: &[u8]) -> Result<FxHashMap<String, Option<Entry>>, ZipError> {
     let mut zip_entries = FxHashMap::default();
     let mut cursor = Cursor::new(data);
 
@@ -126,43 +574,71 @@ pub fn list_zip_entries(data: &[u8]) -> Result<FxHashMap<String, Option<Entry>>,
     Ok(zip_entries)
 }
 
-fn find_central_directory_offset(cursor: &mut Cursor<&[u8]>) -> Result<u64, Box<dyn Error>> {
-    cursor.set_position(cursor.get_ref().len() as u64 - 22);
-    while cursor.position() > 0 {
-        let signature = cursor.read_u32::<LittleEndian>()?;
-        if signature == 0x06054b50 {
-            cursor.set_position(cursor.position() + 12);
-            let central_directory_offset = cursor.read_u32::<LittleEndian>()? as u64;
-            return Ok(central_directory_offset);
-        }
-        cursor.set_position(cursor.position() - 5);
-    }
-    Err("End of central directory record not found.".into())
+fn find_end_of_central_directory(data: &[u8]) -> Result<usize, ZipError> {
+    // The record is 22 bytes long and may be followed by a comment of up to
+    // 65535 bytes, so its signature is searched backwards within that range
+    let search_start = data.len().saturating_sub(22 + u16::MAX as usize);
+    let search_end = data.len().saturating_sub(18);
+
+    let record_offset = data
+        .get(search_start..search_end)
+        .and_then(|window| window.windows(4).rposition(|bytes| bytes == [0x50, 0x4b, 0x05, 0x06]))
+        .ok_or(ZipError::EndOfCentralDirectoryNotFound)?;
+
+    Ok(search_start + record_offset)
+}
+
+// Comments that aren't valid UTF-8 are ignored, as are truncated ones
+fn read_archive_comment(data: &[u8]) -> Result<Option<String>, ZipError> {
+    let record_offset = find_end_of_central_directory(data)?;
+
+    let mut cursor = Cursor::new(data);
+    cursor.set_position(record_offset as u64 + 20);
+
+    let comment_length = cursor.read_u16::<LittleEndian>()? as usize;
+    let comment_start = record_offset + 22;
+
+    let comment = data
+        .get(comment_start..comment_start + comment_length)
+        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+        .filter(|comment| !comment.is_empty());
+
+    Ok(comment.map(str::to_string))
+}
+
+fn find_central_directory_offset(cursor: &mut Cursor<&[u8]>) -> Result<u64, ZipError> {
+    let record_offset = find_end_of_central_directory(cursor.get_ref())?;
+
+    cursor.set_position(record_offset as u64 + 16);
+    let central_directory_offset = cursor.read_u32::<LittleEndian>()? as u64;
+
+    Ok(central_directory_offset)
 }
 
-#[expect(clippy::type_complexity)]
 fn read_central_file_header(
     cursor: &mut Cursor<&[u8]>,
-) -> Result<Option<(String, Option<Entry>)>, Box<dyn Error>> {
+) -> Result<Option<(String, Option<Entry>)>, ZipError> {
     let signature = cursor.read_u32::<LittleEndian>()?;
     if signature != 0x02014b50 {
         return Ok(None);
     }
 
     cursor.set_position(cursor.position() + 4); // skip version made by and version needed to extract
-    cursor.set_position(cursor.position() + 2); // skip general purpose bit flag
+    // When bit 3 is set the crc and sizes are stored in a data descriptor
+    // following the data, and are zero in the local header; the central
+    // directory always has the right values, so they are read from there
+    let _general_purpose_bit_flag = cursor.read_u16::<LittleEndian>()?;
 
     let compression_method = cursor.read_u16::<LittleEndian>()?;
     cursor.set_position(cursor.position() + 4); // skip last mod time and date
 
     let compression = match compression_method {
-        0 => Ok(Compression::Uncompressed),
-        8 => Ok(Compression::Deflate),
-        _ => Err("Oh no"),
-    }
-    .unwrap();
+        0 => Compression::Uncompressed,
+        8 => Compression::Deflate,
+        _ => return Err(ZipError::UnsupportedCompression(compression_method)),
+    };
 
-    let _crc32 = cursor.read_u32::<LittleEndian>()?;
+    let crc32 = cursor.read_u32::<LittleEndian>()?;
     let compressed_size = cursor.read_u32::<LittleEndian>()? as u64;
     let _uncompressed_size = cursor.read_u32::<LittleEndian>()? as u64;
 
@@ -177,7 +653,7 @@ fn read_central_file_header(
 
     let mut file_name_bytes = vec![0; file_name_length];
     cursor.read_exact(&mut file_name_bytes)?;
-    let file_name = String::from_utf8(file_name_bytes)?;
+    let file_name = String::from_utf8(file_name_bytes).map_err(|err| err.utf8_error())?;
 
     if file_name.ends_with('/') {
         return Ok(Some((file_name, None)));
@@ -185,7 +661,16 @@ fn read_central_file_header(
 
     cursor.set_position(cursor.position() + extra_field_length as u64 + comment_length as u64);
 
+    // Only the name and extra field lengths are read from the local header,
+    // as they are needed to find the data and are set even when streaming
     let mut local_file_header_cursor = cursor.clone();
+    local_file_header_cursor.set_position(local_header_offset);
+
+    let local_file_header_signature = local_file_header_cursor.read_u32::<LittleEndian>()?;
+    if local_file_header_signature != 0x04034b50 {
+        return Err(ZipError::InvalidSignature(local_file_header_signature));
+    }
+
     local_file_header_cursor.set_position(local_header_offset + 26);
 
     let local_file_header_file_name_length =
@@ -199,13 +684,18 @@ fn read_central_file_header(
 
     let entry = Entry {
         compression,
-        offset: file_data_offset.try_into()?,
-        size: compressed_size.try_into()?,
+        offset: to_usize(file_data_offset)?,
+        size: to_usize(compressed_size)?,
+        crc32,
     };
 
     Ok(Some((file_name, Some(entry))))
 }
 
+fn to_usize(value: u64) -> Result<usize, ZipError> {
+    value.try_into().map_err(|_| ZipError::IoError(std::io::ErrorKind::InvalidData.into()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -224,6 +714,159 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_counts() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+
+        assert_eq!(zip.entry_count(), 10);
+        assert_eq!(zip.dir_count(), 3);
+        assert_eq!(zip.total_count(), 13);
+        assert!(!zip.is_empty());
+
+        let empty_dir = tempfile::tempdir().unwrap();
+
+        let zip = Zip::from_directory(empty_dir.path()).unwrap();
+        assert_eq!(zip.total_count(), 0);
+        assert!(zip.is_empty());
+    }
+
+    #[test]
+    fn test_debug() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+
+        assert_eq!(format!("{zip:?}"), "Zip { files: 10, dirs: 3 }");
+    }
+
+    #[test]
+    fn test_contains() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+
+        assert!(zip.contains("node_modules/left-pad/index.js"));
+        assert!(zip.contains_file("node_modules/left-pad/index.js"));
+        assert!(!zip.contains_dir("node_modules/left-pad/index.js"));
+
+        for dir in ["node_modules/left-pad", "node_modules/left-pad/"] {
+            assert!(zip.contains(dir));
+            assert!(zip.contains_dir(dir));
+            assert!(!zip.contains_file(dir));
+        }
+
+        assert!(!zip.contains("node_modules/right-pad"));
+        assert!(!zip.contains_file("node_modules/left-pad/missing.js"));
+        assert!(!zip.contains_dir("node_modules/left-pad/missing"));
+    }
+
+    #[test]
+    fn test_read_to_str() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+        let content = zip.read_to_str("node_modules/left-pad/package.json").unwrap();
+
+        assert!(matches!(content, Cow::Borrowed(_)));
+        assert_eq!(content, zip.read_to_string("node_modules/left-pad/package.json").unwrap());
+
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-streamed.zip")).unwrap();
+        let content = zip.read_to_str("node_modules/left-pad/package.json").unwrap();
+
+        assert!(matches!(content, Cow::Owned(_)));
+        assert!(content.contains("\"left-pad\""));
+
+        assert_eq!(
+            zip.read_to_str("node_modules/left-pad/missing.json").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_read_zip_with_comment() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-comment.zip")).unwrap();
+
+        assert_eq!(zip.entry_count(), 10);
+        assert!(zip.read_to_string("node_modules/left-pad/package.json").is_ok());
+
+        assert!(Zip::new(&b"PK\x05\x06"[..]).is_err());
+        assert!(Zip::new(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        fn replace_all(data: &mut [u8], from: &[u8], to: &[u8]) {
+            for i in 0..data.len() - from.len() {
+                if &data[i..i + from.len()] == from {
+                    data[i..i + to.len()].copy_from_slice(to);
+                }
+            }
+        }
+
+        let original = include_bytes!("../fixtures/left-pad-1.zip");
+
+        assert!(matches!(Zip::new(&b""[..]), Err(ZipError::EndOfCentralDirectoryNotFound)));
+
+        let mut data = original.to_vec();
+        replace_all(&mut data, b"PK\x03\x04", b"PK\x03\x00");
+        assert!(matches!(Zip::new(data), Err(ZipError::InvalidSignature(0x00034b50))));
+
+        // The compression method is stored 10 bytes after the start of each
+        // central directory header
+        let mut data = original.to_vec();
+        let header = data.windows(4).position(|bytes| bytes == b"PK\x01\x02").unwrap();
+        data[header + 10] = 99;
+        assert!(matches!(Zip::new(data), Err(ZipError::UnsupportedCompression(99))));
+
+        let err: std::io::Error = std::io::ErrorKind::UnexpectedEof.into();
+        assert!(matches!(ZipError::from(err), ZipError::IoError(_)));
+    }
+
+    #[test]
+    fn test_comment() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-comment.zip")).unwrap();
+        assert_eq!(zip.comment(), Some("x"));
+
+        let mut data = include_bytes!("../fixtures/left-pad-comment.zip").to_vec();
+        *data.last_mut().unwrap() = 0xff;
+
+        let zip = Zip::new(data).unwrap();
+        assert_eq!(zip.comment(), None);
+
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+        assert_eq!(zip.comment(), None);
+    }
+
+    #[test]
+    fn test_read_dir() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+
+        let read_dir = |dir: &str| {
+            let mut entries: Vec<_> = zip
+                .read_dir(dir)
+                .unwrap()
+                .map(|entry| match entry.file_type {
+                    FileType::File => entry.name.to_string(),
+                    FileType::Directory => format!("{}/", entry.name),
+                })
+                .collect();
+
+            entries.sort();
+            entries
+        };
+
+        assert_eq!(read_dir(""), vec!["node_modules/"]);
+        assert_eq!(read_dir("node_modules/"), vec!["left-pad/"]);
+        assert_eq!(
+            read_dir("node_modules/left-pad/perf"),
+            vec!["O(n).js", "es6Repeat.js", "perf.js"]
+        );
+        assert_eq!(read_dir("node_modules/left-pad").len(), 8);
+
+        assert_eq!(
+            zip.read_dir("node_modules/right-pad").err().unwrap().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert!(zip.read_dir("node_modules/left-pad/index.js").is_err());
+
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(zip.read_dir("").unwrap());
+    }
+
     #[test]
     fn test_read_zip_entry_2() {
         let zip = Zip::new(include_bytes!("../fixtures/left-pad-2.zip")).unwrap();
@@ -237,4 +880,138 @@ mod tests {
             vec!["node_modules/", "node_modules/left-pad/", "node_modules/left-pad/perf/",]
         );
     }
+
+    #[test]
+    fn test_diff() {
+        let old = Zip::new(include_bytes!("../fixtures/left-pad-diff-old.zip")).unwrap();
+        let new = Zip::new(include_bytes!("../fixtures/left-pad-diff-new.zip")).unwrap();
+
+        assert_eq!(
+            Zip::diff(&old, &new),
+            ZipDiff {
+                added: vec!["node_modules/left-pad/CHANGELOG.md"],
+                removed: vec!["node_modules/left-pad/README.md"],
+                modified: vec![(
+                    "node_modules/left-pad/package.json",
+                    "node_modules/left-pad/package.json"
+                )],
+            }
+        );
+
+        assert_eq!(Zip::diff(&old, &old), ZipDiff::default());
+    }
+
+    #[test]
+    fn test_read_streamed_zip() {
+        // Entries written with a data descriptor (bit 3 of the general purpose flag)
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-streamed.zip")).unwrap();
+
+        assert_eq!(
+            zip.read_to_string("node_modules/left-pad/package.json").unwrap(),
+            "{\"name\":\"left-pad\",\"version\":\"1.3.0\"}\n"
+        );
+        assert_eq!(
+            zip.read_to_string("node_modules/left-pad/index.js").unwrap(),
+            "module.exports = leftPad;\n".repeat(5)
+        );
+    }
+
+    #[test]
+    fn test_zip_builder() {
+        let index = "module.exports = leftPad;\n".repeat(5);
+
+        let mut builder = ZipBuilder::new();
+        builder
+            .add_dir("node_modules/left-pad")
+            .add_file("node_modules/left-pad/package.json", b"{}", Compression::Uncompressed)
+            .add_file("node_modules/left-pad/index.js", index.as_bytes(), Compression::Deflate)
+            .add_dir("node_modules/left-pad/perf/");
+        let data = builder.finish();
+
+        let zip = Zip::new(data.as_slice()).unwrap();
+
+        assert_eq!(zip.entry_count(), 2);
+        assert_eq!(zip.dir_count(), 3);
+        assert!(zip.contains_dir("node_modules/left-pad/perf"));
+        assert_eq!(zip.comment(), None);
+
+        assert_eq!(
+            zip.read_to_str("node_modules/left-pad/package.json").unwrap(),
+            Cow::Borrowed("{}")
+        );
+        assert_eq!(zip.read_to_string("node_modules/left-pad/index.js").unwrap(), index);
+
+        let entry = &zip.files["node_modules/left-pad/index.js"];
+        assert!(matches!(entry.compression, Compression::Deflate));
+        assert!(entry.size < index.len());
+    }
+
+    #[test]
+    fn test_zip_builder_empty() {
+        let zip = Zip::new(ZipBuilder::new().finish()).unwrap();
+
+        assert!(zip.is_empty());
+    }
+
+    #[test]
+    fn test_verify_integrity() {
+        let zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+        assert!(zip.verify_integrity().is_ok());
+
+        let mut builder = ZipBuilder::new();
+        builder
+            .add_file("a.txt", b"hello world", Compression::Uncompressed)
+            .add_file("b.txt", b"unchanged", Compression::Uncompressed)
+            .add_file("c.txt", "lorem ipsum ".repeat(10).as_bytes(), Compression::Deflate);
+        let mut data = builder.finish();
+
+        let offset = data.windows(11).position(|w| w == b"hello world").unwrap();
+        data[offset] = b'j';
+
+        let zip = Zip::new(data.as_slice()).unwrap();
+        let failures = zip.verify_integrity().unwrap_err();
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "a.txt");
+        assert!(matches!(
+            failures[0].1,
+            IntegrityError::CrcMismatch { expected, actual } if expected != actual
+        ));
+
+        assert!(matches!(
+            Zip::new_with_options(data.as_slice(), &ZipOpenOptions::default()),
+            Err(ZipError::IntegrityCheckFailed(failures)) if failures.len() == 1
+        ));
+
+        let options = ZipOpenOptions { skip_integrity_check: true };
+        assert!(Zip::new_with_options(data.as_slice(), &options).is_ok());
+    }
+
+    #[test]
+    fn test_verify_integrity_out_of_bounds() {
+        let mut zip = Zip::new(include_bytes!("../fixtures/left-pad-1.zip")).unwrap();
+        zip.files.get_mut("node_modules/left-pad/index.js").unwrap().size = usize::MAX;
+
+        let failures = zip.verify_integrity().unwrap_err();
+
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(
+            &failures[0].1,
+            IntegrityError::IoError(err) if err.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let data = include_bytes!("../fixtures/left-pad-1.zip");
+
+        let borrowed = Zip::new(&data[..]).unwrap();
+        let owned = Zip::new(data.to_vec()).unwrap();
+
+        assert!(borrowed.memory_usage() > 13 * std::mem::size_of::<String>());
+        assert_eq!(owned.memory_usage(), data.len() + borrowed.memory_usage());
+
+        let empty = Zip::new(ZipBuilder::new().finish()).unwrap();
+        assert_eq!(empty.memory_usage(), 22);
+    }
 }